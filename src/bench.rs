@@ -0,0 +1,102 @@
+use std::fmt::Display;
+
+use crate::field::Field;
+use crate::solver;
+
+/// The statistics collected by letting the solver play many games
+pub struct BenchReport {
+    pub rows: usize,
+    pub cols: usize,
+    pub mine_percentage: u8,
+    pub wins: usize,
+    pub guesses: usize,
+    /// The 3BV of every board that was played
+    pub bbbvs: Vec<usize>,
+}
+
+impl BenchReport {
+    pub fn games(&self) -> usize {
+        self.bbbvs.len()
+    }
+}
+
+/// Lets the solver play `games` games on a `rows`x`cols` field, with the first click at the center of the field
+pub fn run(rows: usize, cols: usize, mine_percentage: u8, games: usize) -> BenchReport {
+    let mut field = Field::new(rows, cols);
+    let (row, col) = (field.rows / 2, field.cols / 2);
+
+    let mut report = BenchReport {
+        rows: field.rows,
+        cols: field.cols,
+        mine_percentage,
+        wins: 0,
+        guesses: 0,
+        bbbvs: Vec::with_capacity(games),
+    };
+
+    for _ in 0..games {
+        field.reset();
+        field.randomize(mine_percentage, row, col);
+        report.bbbvs.push(field.bbbv());
+
+        field.uncover_at(row, col);
+        let game = solver::play(&mut field);
+
+        report.guesses += game.guesses;
+        if game.won {
+            report.wins += 1;
+        }
+    }
+    report
+}
+
+impl Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const BUCKETS: usize = 10;
+        const BAR_WIDTH: usize = 40;
+
+        let games = self.games().max(1) as f64;
+        writeln!(
+            f,
+            "{} games on a {}x{} field with {}% mines",
+            self.games(),
+            self.cols,
+            self.rows,
+            self.mine_percentage
+        )?;
+        writeln!(
+            f,
+            "Win rate:          {:.1}%",
+            self.wins as f64 / games * 100.0
+        )?;
+        writeln!(f, "Guesses per game:  {:.2}", self.guesses as f64 / games)?;
+        writeln!(
+            f,
+            "Mean 3BV:          {:.1}",
+            self.bbbvs.iter().sum::<usize>() as f64 / games
+        )?;
+
+        let (Some(&min), Some(&max)) = (self.bbbvs.iter().min(), self.bbbvs.iter().max()) else {
+            return Ok(());
+        };
+
+        writeln!(f, "3BV distribution:")?;
+        let bucket_size = ((max - min) / BUCKETS + 1).max(1);
+        let mut counts = vec![0; (max - min) / bucket_size + 1];
+        for bbbv in &self.bbbvs {
+            counts[(bbbv - min) / bucket_size] += 1;
+        }
+
+        let highest = *counts.iter().max().unwrap_or(&1);
+        for (i, count) in counts.iter().enumerate() {
+            let from = min + i * bucket_size;
+            let to = from + bucket_size - 1;
+            writeln!(
+                f,
+                "{from:>5}-{to:<5} {:<BAR_WIDTH$} {count}",
+                "#".repeat(count * BAR_WIDTH / highest),
+            )?;
+        }
+        Ok(())
+    }
+}
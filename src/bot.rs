@@ -0,0 +1,227 @@
+//! Lets an external program play the game through a line-based text protocol over its stdin/stdout.
+//!
+//! Protocol version 1. Every message is a single line terminated by `\n`. Rows and columns are 0-based.
+//!
+//! The game sends:
+//! - `mnswpr <version>` once, as soon as the engine is started
+//! - `game <rows> <cols>` at the start of each game
+//! - `board <mines> <flags>` followed by `<rows>` lines of `<cols>` chars each, whenever an action is expected.
+//!   `.` is a closed cell, `F` is a flagged cell and a digit is an open cell with that many neighbouring mines.
+//!   Note that the mines are placed after the first `open`, so `<mines>` is 0 until then
+//! - `error <message>` if the last action was not valid. The board is sent again right after
+//! - `result <won|lost|quit>` at the end of each game
+//! - `bye` after the last game
+//!
+//! The engine answers every board with exactly one action:
+//! - `open <row> <col>` uncovers a cell
+//! - `flag <row> <col>` flags or unflags a closed cell. Unless the game was started with `--flag-limit off`,
+//!   a closed cell can only be flagged while there are fewer flags than mines
+//! - `chord <row> <col>` uncovers the closed neighbours of an open cell, if it has as many flagged neighbours as its number
+//! - `quit` gives up the current game
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::field::Field;
+use crate::mnswpr::Outcome;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+pub enum Action {
+    Open(usize, usize),
+    Flag(usize, usize),
+    Chord(usize, usize),
+    Quit,
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let name = words.next().ok_or("empty action")?;
+
+        let action: fn(usize, usize) -> Action = match name {
+            "quit" => return Ok(Action::Quit),
+            "open" => Action::Open,
+            "flag" => Action::Flag,
+            "chord" => Action::Chord,
+            v => {
+                return Err(format!(
+                    "expected one of \"open\", \"flag\", \"chord\", \"quit\". Got \"{v}\""
+                ))
+            }
+        };
+
+        let mut position = || -> Result<usize, String> {
+            words
+                .next()
+                .ok_or(format!("missing position for \"{name}\""))?
+                .parse()
+                .map_err(|_| format!("invalid position for \"{name}\""))
+        };
+        let row = position()?;
+        let col = position()?;
+
+        Ok(action(row, col))
+    }
+}
+
+/// An external program playing the game
+pub struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Engine {
+    /// Spawns `cmd` through the shell and sends it the protocol version
+    pub fn spawn(cmd: &str) -> Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(cmd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Could not start the engine \"{cmd}\""))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+
+        let mut engine = Self {
+            child,
+            stdin,
+            stdout,
+        };
+        engine.send(&format!("mnswpr {PROTOCOL_VERSION}"))?;
+        Ok(engine)
+    }
+
+    fn send(&mut self, line: &str) -> Result<()> {
+        writeln!(self.stdin, "{line}").context("Could not write to the engine")?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<String> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            bail!("The engine closed its output");
+        }
+        Ok(line.trim().to_string())
+    }
+
+    fn send_board(&mut self, field: &Field) -> Result<()> {
+        let mut msg = format!("board {} {}", field.mine_count, field.flag_count);
+
+        for row in 0..field.rows {
+            msg.push('\n');
+            for col in 0..field.cols {
+                let cell = field.get_unchecked(row, col);
+                msg.push(if cell.is_open() {
+                    char::from_digit(cell.neighbouring_bomb_count as u32, 10).unwrap_or('?')
+                } else if cell.is_flagged() {
+                    'F'
+                } else {
+                    '.'
+                });
+            }
+        }
+        self.send(&msg)
+    }
+
+    /// Plays a whole game on `field`, which gets randomized on the first `open`.
+    /// Returns how the game ended
+    pub fn play(&mut self, field: &mut Field, mine_percentage: u8) -> Result<Outcome> {
+        field.reset();
+        self.send(&format!("game {} {}", field.rows, field.cols))?;
+
+        let mut first_move = true;
+
+        let outcome = loop {
+            self.send_board(field)?;
+
+            let line = self.receive()?;
+            let action = match line.parse::<Action>() {
+                Ok(action) => action,
+                Err(e) => {
+                    self.send(&format!("error {e}"))?;
+                    continue;
+                }
+            };
+
+            let exploded = match action {
+                Action::Quit => break Outcome::Quit,
+                Action::Open(row, col) => {
+                    if first_move && field.get(row, col).is_some() {
+                        field.randomize(mine_percentage, row, col);
+                        first_move = false;
+                    }
+                    field.uncover_at(row, col)
+                }
+                Action::Flag(_, _) if first_move => {
+                    self.send("error the first action must be \"open\"")?;
+                    continue;
+                }
+                Action::Flag(row, col)
+                    if !field.can_flag()
+                        && field.get(row, col).is_some_and(|cell| cell.is_closed()) =>
+                {
+                    self.send("error there are already as many flags as mines")?;
+                    continue;
+                }
+                Action::Flag(row, col) => {
+                    let flagged = field.toggle_flag_at(row, col);
+                    // Engines only flag and unflag, question marks are for human players
+                    if field.get(row, col).is_some_and(|cell| cell.is_questioned()) {
+                        field.toggle_flag_at(row, col);
+                    }
+                    flagged.map(|_| false)
+                }
+                Action::Chord(row, col) => match field.get(row, col) {
+                    Some(cell)
+                        if cell.is_open()
+                            && field.get_flagged_nbors_amt(row, col)
+                                == Some(cell.neighbouring_bomb_count) =>
+                    {
+                        field.uncover_around_cell_at(row, col, false)
+                    }
+                    Some(_) => {
+                        self.send("error the cell must be open and have as many flags around it as its number")?;
+                        continue;
+                    }
+                    None => None,
+                },
+            };
+
+            match exploded {
+                None => self.send("error position out of bounds")?,
+                Some(true) => break Outcome::Lost,
+                Some(false) if field.closed_empty_cells == 0 => break Outcome::Won,
+                Some(false) => {}
+            }
+        };
+
+        let result = match outcome {
+            Outcome::Won => "won",
+            Outcome::Lost | Outcome::Guessed => "lost",
+            Outcome::Quit | Outcome::NewGame | Outcome::Restart | Outcome::RaceOver => "quit",
+        };
+        self.send(&format!("result {result}"))?;
+        Ok(outcome)
+    }
+
+    /// Tells the engine that there are no more games and waits for it to exit
+    pub fn finish(mut self) -> Result<()> {
+        self.send("bye")?;
+        drop(self.stdin);
+        let status = self.child.wait()?;
+        if !status.success() {
+            return Err(anyhow!("The engine exited with {status}"));
+        }
+        Ok(())
+    }
+}
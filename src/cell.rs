@@ -1,30 +1,25 @@
-use crate::colors;
+use termion::style;
+use unicode_width::UnicodeWidthStr;
 
-#[derive(Copy, Clone, Debug)]
+use crate::colors::{self, Accessibility, ATTR_RESET};
+
+#[derive(Copy, Clone, Debug, Default)]
 pub enum State {
     Open,
+    #[default]
     Closed,
     Flagged,
+    /// Closed, with a mark the player puts on cells they are not sure about
+    Questioned,
 }
 
-impl Default for State {
-    fn default() -> Self {
-        Self::Closed
-    }
-}
-
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Default)]
 pub enum Content {
     Mine,
+    #[default]
     Empty,
 }
 
-impl Default for Content {
-    fn default() -> Self {
-        Self::Empty
-    }
-}
-
 #[derive(Copy, Clone, Debug, Default)]
 pub struct Cell {
     pub state: State,
@@ -41,115 +36,247 @@ impl Cell {
         matches!(self.state, State::Open)
     }
 
+    /// Returns whether the cell is closed and not flagged. Questioned cells are closed
     pub fn is_closed(&self) -> bool {
-        matches!(self.state, State::Closed)
+        matches!(self.state, State::Closed | State::Questioned)
     }
 
     pub fn is_flagged(&self) -> bool {
         matches!(self.state, State::Flagged)
     }
 
+    pub fn is_questioned(&self) -> bool {
+        matches!(self.state, State::Questioned)
+    }
+
     pub fn contains_mine(&self) -> bool {
         matches!(self.content, Content::Mine)
     }
 
-    /// This method does not reset the fg/bg color!!
-    pub fn to_string_with_palette(&self, palette: &colors::Palette, with_cursor: bool) -> String {
-        let sep = if with_cursor { ('[', ']') } else { (' ', ' ') };
-        let cursor = (
-            format!("{}{}", palette.cursor_fg.0, sep.0),
-            format!("{}{}", palette.cursor_fg.0, sep.1),
-        );
-
-        let bg;
-        let fg;
-        let repr;
-
-        match self.state {
+    /// Returns how the cell looks while the game is being played
+    pub fn look(self, palette: &colors::Palette) -> Look<'_> {
+        let glyphs = &palette.glyphs;
+
+        let mut look = match self.state {
             State::Open => match self.content {
-                Content::Mine => {
-                    bg = &palette.mine.bg;
-                    fg = &palette.mine.fg;
-                    repr = "*".to_string();
-                }
-                Content::Empty => {
-                    bg = &palette.open_bg;
-                    fg = &palette.neighbour_count_to_fg_color[self.neighbouring_bomb_count];
-                    repr = if self.neighbouring_bomb_count != 0 {
-                        self.neighbouring_bomb_count.to_string()
-                    } else {
-                        " ".to_string()
-                    };
-                }
+                Content::Mine => Look {
+                    bold: true,
+                    ..Look::new(&palette.mine.bg, &palette.mine.fg, glyphs.pad(&glyphs.mine))
+                },
+                Content::Empty => self.open_empty_look(palette),
+            },
+            State::Closed => Look::new(
+                &palette.closed.bg,
+                &palette.closed.fg,
+                glyphs.pad(&glyphs.closed),
+            ),
+            State::Flagged => Look {
+                invert: true,
+                ..Look::new(&palette.flag.bg, &palette.flag.fg, glyphs.pad(&glyphs.flag))
+            },
+            State::Questioned => Look {
+                underline: true,
+                ..Look::new(
+                    &palette.questioned.bg,
+                    &palette.questioned.fg,
+                    glyphs.pad(&glyphs.questioned),
+                )
             },
-            State::Closed => {
-                bg = &palette.closed.bg;
-                fg = &palette.closed.fg;
-                repr = ".".to_string();
+        };
+
+        if palette.accessibility == Accessibility::Off {
+            look.clear_attributes();
+        }
+        look
+    }
+
+    /// Returns how the cell looks on the end screen, where every cell is shown open
+    /// together with the status of the flags (placed correctly on a mine or placed on an empty cell)
+    pub fn look_lost(self, palette: &colors::Palette) -> Look<'_> {
+        let glyphs = &palette.glyphs;
+        let accessible = palette.accessibility != Accessibility::Off;
+
+        let mut look = match (self.state, self.content) {
+            (State::Flagged, Content::Mine) => {
+                let (bg, fg) = (&palette.correct_flag.bg, &palette.correct_flag.fg);
+                if accessible {
+                    Look {
+                        bold: true,
+                        underline: true,
+                        ..Look::new(bg, fg, glyphs.pad(&glyphs.correct_flag))
+                    }
+                } else {
+                    Look::new(bg, fg, glyphs.pad(&glyphs.mine))
+                }
             }
-            State::Flagged => {
-                bg = &palette.flag.bg;
-                fg = &palette.flag.fg;
-                repr = "F".to_string();
+            (State::Flagged, Content::Empty) => {
+                let (bg, fg) = (&palette.wrong_flag.bg, &palette.wrong_flag.fg);
+                if accessible {
+                    Look {
+                        invert: true,
+                        ..Look::new(bg, fg, glyphs.pad(&glyphs.wrong_flag))
+                    }
+                } else {
+                    Look::new(
+                        bg,
+                        fg,
+                        glyphs.pad(&self.neighbouring_bomb_count.to_string()),
+                    )
+                }
             }
+            (_, Content::Mine) => Look {
+                bold: true,
+                ..Look::new(&palette.mine.bg, &palette.mine.fg, glyphs.pad(&glyphs.mine))
+            },
+            (_, Content::Empty) => self.open_empty_look(palette),
         };
-        format!(
-            "{bg}{cursor0}{fg}{repr}{cursor1}",
-            bg = bg.0,
-            fg = fg.0,
-            cursor0 = cursor.0,
-            cursor1 = cursor.1,
+
+        if !accessible {
+            look.clear_attributes();
+        }
+        look
+    }
+
+    fn open_empty_look(self, palette: &colors::Palette) -> Look<'_> {
+        let glyphs = &palette.glyphs;
+        let bg = &palette.open_bg;
+        let fg = &palette.neighbour_count_to_fg_color[self.neighbouring_bomb_count];
+        if self.neighbouring_bomb_count != 0 {
+            Look {
+                bold: true,
+                ..Look::new(
+                    bg,
+                    fg,
+                    glyphs.pad(&self.neighbouring_bomb_count.to_string()),
+                )
+            }
+        } else {
+            Look::new(bg, fg, glyphs.pad(""))
+        }
+    }
+
+    /// This method does not reset the fg/bg color!!
+    pub fn to_string_with_palette(self, palette: &colors::Palette, with_cursor: bool) -> String {
+        format_cell(&cursor_reprs(palette, with_cursor), &self.look(palette))
+    }
+
+    /// Like `to_string_with_palette`, with the glyph inverted to draw attention to the cell
+    pub fn to_string_with_palette_flashing(
+        self,
+        palette: &colors::Palette,
+        with_cursor: bool,
+    ) -> String {
+        let mut look = self.look(palette);
+        look.invert = !look.invert;
+        format_cell(&cursor_reprs(palette, with_cursor), &look)
+    }
+
+    /// Like `to_string_with_palette`, with the cursor of one of the players of a co-op game, drawn in `cursor_fg`
+    pub fn to_string_with_player_cursor(
+        self,
+        palette: &colors::Palette,
+        cursor_fg: &colors::CFg,
+    ) -> String {
+        format_cell(
+            &colored_cursor_reprs(palette, cursor_fg, true),
+            &self.look(palette),
         )
     }
 
     pub fn to_string_with_palette_lost(
-        &self,
+        self,
         palette: &colors::Palette,
         with_cursor: bool,
     ) -> String {
-        let sep = if with_cursor { ('[', ']') } else { (' ', ' ') };
-        let cursor = (
-            format!("{}{}", palette.cursor_fg.0, sep.0),
-            format!("{}{}", palette.cursor_fg.0, sep.1),
-        );
+        format_cell(
+            &cursor_reprs(palette, with_cursor),
+            &self.look_lost(palette),
+        )
+    }
+}
 
-        let bg;
-        let fg;
-        let repr;
+/// How a cell looks: its colors, and the glyph drawn in it with its text attributes.
+/// The attributes are only used in the accessibility modes
+pub struct Look<'a> {
+    pub bg: &'a colors::CBg,
+    pub fg: &'a colors::CFg,
+    /// The glyph, padded to the width of the content of the cell
+    pub repr: String,
+    pub bold: bool,
+    pub underline: bool,
+    pub invert: bool,
+}
 
-        match (self.state, self.content) {
-            (State::Flagged, Content::Mine) => {
-                bg = &palette.correct_flag.bg;
-                fg = &palette.correct_flag.fg;
-                repr = "*".to_string();
-            }
-            (State::Flagged, Content::Empty) => {
-                bg = &palette.wrong_flag.bg;
-                fg = &palette.wrong_flag.fg;
-                repr = self.neighbouring_bomb_count.to_string();
-            }
-            (_, Content::Mine) => {
-                bg = &palette.mine.bg;
-                fg = &palette.mine.fg;
-                repr = "*".to_string();
-            }
-            (_, Content::Empty) => {
-                bg = &palette.open_bg;
-                fg = &palette.neighbour_count_to_fg_color[self.neighbouring_bomb_count];
-                repr = if self.neighbouring_bomb_count != 0 {
-                    self.neighbouring_bomb_count.to_string()
-                } else {
-                    " ".to_string()
-                };
-            }
-        };
+impl<'a> Look<'a> {
+    fn new(bg: &'a colors::CBg, fg: &'a colors::CFg, repr: String) -> Self {
+        Self {
+            bg,
+            fg,
+            repr,
+            bold: false,
+            underline: false,
+            invert: false,
+        }
+    }
 
-        format!(
-            "{bg}{cursor0}{fg}{repr}{cursor1}",
-            bg = bg.0,
-            fg = fg.0,
-            cursor0 = cursor.0,
-            cursor1 = cursor.1,
-        )
+    fn clear_attributes(&mut self) {
+        self.bold = false;
+        self.underline = false;
+        self.invert = false;
     }
+
+    /// Returns the escape sequences that turn on the text attributes of the glyph
+    fn attributes(&self) -> String {
+        let mut attr = String::new();
+        if self.bold {
+            attr.push_str(style::Bold.as_ref());
+        }
+        if self.underline {
+            attr.push_str(style::Underline.as_ref());
+        }
+        if self.invert {
+            attr.push_str(style::Invert.as_ref());
+        }
+        attr
+    }
+}
+/// Returns the strings drawn at the left and at the right of a cell, which are the cursor
+/// if `with_cursor == true`, otherwise blanks as wide as the cursor
+fn cursor_reprs(palette: &colors::Palette, with_cursor: bool) -> (String, String) {
+    colored_cursor_reprs(palette, &palette.cursor_fg, with_cursor)
+}
+
+/// Like `cursor_reprs`, with the cursor drawn in `cursor_fg`
+fn colored_cursor_reprs(
+    palette: &colors::Palette,
+    cursor_fg: &colors::CFg,
+    with_cursor: bool,
+) -> (String, String) {
+    let glyphs = &palette.glyphs;
+    let (left, right) = if with_cursor {
+        (
+            glyphs.cursor_left.to_string(),
+            glyphs.cursor_right.to_string(),
+        )
+    } else {
+        (
+            " ".repeat(glyphs.cursor_left.width()),
+            " ".repeat(glyphs.cursor_right.width()),
+        )
+    };
+    (format!("{cursor_fg}{left}"), format!("{cursor_fg}{right}"))
+}
+
+/// Puts together the representation of a cell. The attributes only apply to the glyph, and get reset right after it
+fn format_cell(cursor: &(String, String), look: &Look) -> String {
+    let attr = look.attributes();
+    let attr_reset = if attr.is_empty() { "" } else { ATTR_RESET };
+    format!(
+        "{bg}{cursor0}{fg}{attr}{repr}{attr_reset}{cursor1}",
+        bg = look.bg,
+        fg = look.fg,
+        repr = look.repr,
+        cursor0 = cursor.0,
+        cursor1 = cursor.1,
+    )
 }
@@ -1,9 +1,179 @@
 use serde::de::{self, Visitor};
 use serde::{Deserialize, Deserializer};
-use std::fmt;
+use std::env;
+use std::fmt::{self, Display};
+use std::str::FromStr;
 use termion::color;
 
-#[derive(Deserialize, Debug)]
+use crate::glyphs::{Glyphs, ASCII_GLYPHS};
+
+/// A color, together with the kind of escape sequence used to print it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Color {
+    Rgb(u8, u8, u8),
+    /// One of the 256 colors of xterm
+    Ansi256(u8),
+    /// One of the 16 basic colors, which are supported by almost every terminal
+    Ansi16(u8),
+    /// No color at all, the terminal default is used
+    None,
+}
+
+/// The 16 basic colors, with the default values used by xterm
+const ANSI16_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The intensity of each of the 6 levels of the color cube of the 256 colors
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+impl Color {
+    /// Returns the closest color that can be printed in `mode`
+    pub fn quantized(self, mode: ColorMode) -> Self {
+        let Color::Rgb(r, g, b) = self else {
+            return self;
+        };
+
+        match mode {
+            ColorMode::TrueColor => self,
+            ColorMode::Ansi256 => {
+                let nearest_level = |v: u8| {
+                    (0..6)
+                        .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - v as i32).abs())
+                        .unwrap()
+                };
+                let (ri, gi, bi) = (nearest_level(r), nearest_level(g), nearest_level(b));
+                let cube = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+
+                // The grayscale ramp goes from 8 to 238 in steps of 10
+                let gray_idx = ((r as u32 + g as u32 + b as u32) / 3)
+                    .saturating_sub(3)
+                    .min(233)
+                    / 10;
+                let gray_value = (8 + gray_idx * 10) as u8;
+                let gray = (gray_value, gray_value, gray_value);
+
+                if distance((r, g, b), gray) < distance((r, g, b), cube) {
+                    Color::Ansi256(232 + gray_idx as u8)
+                } else {
+                    Color::Ansi256((16 + 36 * ri + 6 * gi + bi) as u8)
+                }
+            }
+            ColorMode::Ansi16 => Color::Ansi16(
+                (0..16)
+                    .min_by_key(|&i| distance((r, g, b), ANSI16_RGB[i]))
+                    .unwrap() as u8,
+            ),
+            ColorMode::Monochrome => Color::None,
+        }
+    }
+
+    /// Returns the red, green and blue components of the color as usually shown by terminals.
+    /// Returns None for `Color::None`, whose actual value depends on the terminal
+    pub fn to_rgb(self) -> Option<(u8, u8, u8)> {
+        match self {
+            Color::Rgb(r, g, b) => Some((r, g, b)),
+            Color::Ansi256(v @ 0..=15) | Color::Ansi16(v) => ANSI16_RGB.get(v as usize).copied(),
+            Color::Ansi256(v @ 16..=231) => {
+                let v = v as usize - 16;
+                Some((
+                    CUBE_LEVELS[v / 36],
+                    CUBE_LEVELS[v / 6 % 6],
+                    CUBE_LEVELS[v % 6],
+                ))
+            }
+            Color::Ansi256(v) => {
+                let gray = 8 + (v - 232) * 10;
+                Some((gray, gray, gray))
+            }
+            Color::None => None,
+        }
+    }
+}
+
+/// How many colors the terminal can show
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorMode {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    Monochrome,
+}
+
+impl ColorMode {
+    /// Guesses what the terminal supports from the `NO_COLOR`, `COLORTERM` and `TERM` environment variables
+    pub fn detect() -> Self {
+        if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return ColorMode::Monochrome;
+        }
+
+        let colorterm = env::var("COLORTERM").unwrap_or_default().to_lowercase();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return ColorMode::TrueColor;
+        }
+
+        let term = env::var("TERM").unwrap_or_default().to_lowercase();
+        if term.is_empty() || term == "dumb" {
+            ColorMode::Monochrome
+        } else if term.contains("truecolor") || term.contains("direct") {
+            ColorMode::TrueColor
+        } else if term.contains("256") {
+            ColorMode::Ansi256
+        } else {
+            ColorMode::Ansi16
+        }
+    }
+}
+
+impl Display for ColorMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorMode::TrueColor => "truecolor",
+            ColorMode::Ansi256 => "256",
+            ColorMode::Ansi16 => "16",
+            ColorMode::Monochrome => "mono",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "truecolor" | "24bit" => Ok(ColorMode::TrueColor),
+            "256" => Ok(ColorMode::Ansi256),
+            "16" => Ok(ColorMode::Ansi16),
+            "mono" | "monochrome" => Ok(ColorMode::Monochrome),
+            v => Err(format!(
+                "Expected one of \"truecolor\", \"256\", \"16\", \"mono\". Got \"{v}\""
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct PaletteElement {
     pub fg: CFg,
     pub bg: CBg,
@@ -12,27 +182,51 @@ pub struct PaletteElement {
 impl PaletteElement {
     pub const fn new(bg: color::Rgb, fg: color::Rgb) -> Self {
         Self {
-            fg: CFg(color::Fg(fg)),
-            bg: CBg(color::Bg(bg)),
+            fg: CFg::new(fg),
+            bg: CBg::new(bg),
         }
     }
 }
 
-#[derive(Debug)]
-pub struct CFg(pub color::Fg<color::Rgb>);
+#[derive(Clone, Debug)]
+pub struct CFg(pub Color);
 
 impl CFg {
     pub const fn new(clr: color::Rgb) -> Self {
-        Self(color::Fg(clr))
+        Self(Color::Rgb(clr.0, clr.1, clr.2))
+    }
+}
+
+impl Display for CFg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Color::Rgb(r, g, b) => color::Fg(color::Rgb(r, g, b)).fmt(f),
+            Color::Ansi256(v) => color::Fg(color::AnsiValue(v)).fmt(f),
+            Color::Ansi16(v) if v < 8 => write!(f, "\x1b[{}m", 30 + v),
+            Color::Ansi16(v) => write!(f, "\x1b[{}m", 90 + v - 8),
+            Color::None => Ok(()),
+        }
     }
 }
 
-#[derive(Debug)]
-pub struct CBg(pub color::Bg<color::Rgb>);
+#[derive(Clone, Debug)]
+pub struct CBg(pub Color);
 
 impl CBg {
     pub const fn new(clr: color::Rgb) -> Self {
-        Self(color::Bg(clr))
+        Self(Color::Rgb(clr.0, clr.1, clr.2))
+    }
+}
+
+impl Display for CBg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Color::Rgb(r, g, b) => color::Bg(color::Rgb(r, g, b)).fmt(f),
+            Color::Ansi256(v) => color::Bg(color::AnsiValue(v)).fmt(f),
+            Color::Ansi16(v) if v < 8 => write!(f, "\x1b[{}m", 40 + v),
+            Color::Ansi16(v) => write!(f, "\x1b[{}m", 100 + v - 8),
+            Color::None => Ok(()),
+        }
     }
 }
 
@@ -54,45 +248,208 @@ impl<'de> Deserialize<'de> for CBg {
     }
 }
 
+/// The colors that can be used by name in a theme, with their CSS values
+const NAMED_COLORS: [(&str, (u8, u8, u8)); 20] = [
+    ("black", (0, 0, 0)),
+    ("white", (255, 255, 255)),
+    ("gray", (128, 128, 128)),
+    ("grey", (128, 128, 128)),
+    ("silver", (192, 192, 192)),
+    ("red", (255, 0, 0)),
+    ("maroon", (128, 0, 0)),
+    ("orange", (255, 165, 0)),
+    ("yellow", (255, 255, 0)),
+    ("olive", (128, 128, 0)),
+    ("lime", (0, 255, 0)),
+    ("green", (0, 128, 0)),
+    ("teal", (0, 128, 128)),
+    ("cyan", (0, 255, 255)),
+    ("blue", (0, 0, 255)),
+    ("navy", (0, 0, 128)),
+    ("purple", (128, 0, 128)),
+    ("magenta", (255, 0, 255)),
+    ("pink", (255, 192, 203)),
+    ("brown", (165, 42, 42)),
+];
+
 struct HexColorVisitor;
 impl<'de> Visitor<'de> for HexColorVisitor {
     type Value = color::Rgb;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str("a color in the format #rrggbb")
+        formatter.write_str("a color in the format #rrggbb or #rgb, or a color name")
     }
 
     fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        let s = v.to_lowercase();
-        if s.len() != 7 {
-            return Err(E::custom("hex color must have lenght 7"));
+        let Some(digits) = v.strip_prefix('#') else {
+            return NAMED_COLORS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(v))
+                .map(|(_, (r, g, b))| color::Rgb(*r, *g, *b))
+                .ok_or_else(|| {
+                    E::custom(format!(
+                        "\"{v}\" is not a color name, and hex colors must start with '#'"
+                    ))
+                });
+        };
+        if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+            return Err(E::custom(format!(
+                "\"{v}\" contains '{c}', which is not a hexadecimal digit"
+            )));
         }
 
-        let r = u8::from_str_radix(&s[1..3], 16);
-        let g = u8::from_str_radix(&s[3..5], 16);
-        let b = u8::from_str_radix(&s[5..7], 16);
-
-        if r.is_err() || g.is_err() || b.is_err() {
-            return Err(E::custom("color values out of range"));
+        let channel = |i: usize, len: usize| u8::from_str_radix(&digits[i..i + len], 16).unwrap();
+        match digits.len() {
+            // #rgb is the same as #rrggbb
+            3 => Ok(color::Rgb(
+                channel(0, 1) * 17,
+                channel(1, 1) * 17,
+                channel(2, 1) * 17,
+            )),
+            6 => Ok(color::Rgb(channel(0, 2), channel(2, 2), channel(4, 2))),
+            len => Err(E::custom(format!(
+                "\"{v}\" has {len} digits, expected a color in the format #rrggbb or #rgb"
+            ))),
         }
+    }
+}
 
-        Ok(color::Rgb(r.unwrap(), g.unwrap(), b.unwrap()))
+/// Whether cells should be told apart by their glyphs and text attributes instead of relying on colors only
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Accessibility {
+    /// Cells are told apart by their colors
+    #[default]
+    Off,
+    /// Distinct glyphs and attributes for every state, and flag colors that can be told apart with any color vision
+    Colorblind,
+    /// Distinct glyphs and attributes for every state, and no colors at all
+    NoColor,
+}
+
+impl Display for Accessibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Accessibility::Off => "off",
+            Accessibility::Colorblind => "colorblind",
+            Accessibility::NoColor => "no-color",
+        };
+        write!(f, "{name}")
     }
 }
 
-#[derive(Deserialize, Debug)]
+impl FromStr for Accessibility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(Accessibility::Off),
+            "colorblind" => Ok(Accessibility::Colorblind),
+            "no-color" => Ok(Accessibility::NoColor),
+            v => Err(format!(
+                "Expected one of \"off\", \"colorblind\", \"no-color\". Got \"{v}\""
+            )),
+        }
+    }
+}
+
+/// The number of cursor colors for the players of a co-op game
+pub const PLAYER_CURSORS: usize = 6;
+
+/// The default cursor colors of the players of a co-op game, far enough apart to be told from each other
+const DEFAULT_PLAYER_CURSOR_FG: [CFg; PLAYER_CURSORS] = [
+    CFg::new(color::Rgb(255, 80, 80)),
+    CFg::new(color::Rgb(0, 200, 255)),
+    CFg::new(color::Rgb(255, 200, 0)),
+    CFg::new(color::Rgb(80, 220, 80)),
+    CFg::new(color::Rgb(230, 80, 230)),
+    CFg::new(color::Rgb(255, 140, 0)),
+];
+
+#[derive(Clone, Debug)]
 pub struct Palette {
     pub closed: PaletteElement,
     pub open_bg: CBg,
     pub neighbour_count_to_fg_color: [CFg; 9],
     pub mine: PaletteElement,
     pub flag: PaletteElement,
+    pub questioned: PaletteElement,
     pub cursor_fg: CFg,
+    /// The cursor colors of the players of a co-op game, by player number. They repeat when there are more players
+    pub player_cursor_fg: [CFg; PLAYER_CURSORS],
     pub correct_flag: PaletteElement,
     pub wrong_flag: PaletteElement,
+    pub glyphs: Glyphs,
+    pub accessibility: Accessibility,
+}
+
+impl Palette {
+    /// Replaces every color of the palette with the closest one that can be printed in `mode`
+    pub fn quantized(mut self, mode: ColorMode) -> Self {
+        let fgs = [
+            &mut self.closed.fg,
+            &mut self.mine.fg,
+            &mut self.flag.fg,
+            &mut self.cursor_fg,
+        ]
+        .into_iter()
+        .chain([
+            &mut self.questioned.fg,
+            &mut self.correct_flag.fg,
+            &mut self.wrong_flag.fg,
+        ])
+        .chain(self.neighbour_count_to_fg_color.iter_mut())
+        .chain(self.player_cursor_fg.iter_mut());
+        for fg in fgs {
+            fg.0 = fg.0.quantized(mode);
+        }
+
+        let bgs = [
+            &mut self.closed.bg,
+            &mut self.open_bg,
+            &mut self.mine.bg,
+            &mut self.flag.bg,
+            &mut self.questioned.bg,
+            &mut self.correct_flag.bg,
+            &mut self.wrong_flag.bg,
+        ];
+        for bg in bgs {
+            bg.0 = bg.0.quantized(mode);
+        }
+        self
+    }
+}
+
+impl Palette {
+    /// Switches the palette to `accessibility`.
+    /// In colorblind mode, the flags on the end screen use blue and orange instead of green and red,
+    /// and closed cells get a glyph color that stands out from their background
+    pub fn with_accessibility(mut self, accessibility: Accessibility) -> Self {
+        self.accessibility = accessibility;
+
+        match accessibility {
+            Accessibility::Off => self,
+            Accessibility::Colorblind => {
+                self.correct_flag.bg = CBg::new(color::Rgb(0, 114, 178));
+                self.wrong_flag.bg = CBg::new(color::Rgb(230, 159, 0));
+                self.correct_flag.fg = CFg::new(color::Rgb(255, 255, 255));
+                self.wrong_flag.fg = CFg::new(color::Rgb(0, 0, 0));
+
+                if let Color::Rgb(r, g, b) = self.closed.bg.0 {
+                    let luminance = 299 * r as u32 + 587 * g as u32 + 114 * b as u32;
+                    self.closed.fg = if luminance > 128_000 {
+                        CFg::new(color::Rgb(0, 0, 0))
+                    } else {
+                        CFg::new(color::Rgb(255, 255, 255))
+                    };
+                }
+                self
+            }
+            Accessibility::NoColor => self.quantized(ColorMode::Monochrome),
+        }
+    }
 }
 
 pub const OG_PALETTE: Palette = Palette {
@@ -111,9 +468,13 @@ pub const OG_PALETTE: Palette = Palette {
     ],
     mine: PaletteElement::new(color::Rgb(180, 0, 0), color::Rgb(255, 255, 255)),
     flag: PaletteElement::new(color::Rgb(40, 100, 40), color::Rgb(255, 255, 255)),
+    questioned: PaletteElement::new(color::Rgb(30, 30, 30), color::Rgb(255, 200, 0)),
     cursor_fg: CFg::new(color::Rgb(255, 255, 255)),
+    player_cursor_fg: DEFAULT_PLAYER_CURSOR_FG,
     correct_flag: PaletteElement::new(color::Rgb(0, 255, 0), color::Rgb(255, 255, 255)),
     wrong_flag: PaletteElement::new(color::Rgb(255, 0, 0), color::Rgb(255, 255, 255)),
+    glyphs: ASCII_GLYPHS,
+    accessibility: Accessibility::Off,
 };
 
 pub const MNSWPR_PALETTE: Palette = Palette {
@@ -132,10 +493,95 @@ pub const MNSWPR_PALETTE: Palette = Palette {
     ],
     mine: PaletteElement::new(color::Rgb(180, 0, 0), color::Rgb(255, 255, 255)),
     flag: PaletteElement::new(color::Rgb(40, 100, 40), color::Rgb(255, 255, 255)),
+    questioned: PaletteElement::new(color::Rgb(30, 30, 30), color::Rgb(255, 200, 0)),
     cursor_fg: CFg::new(color::Rgb(255, 255, 255)),
+    player_cursor_fg: DEFAULT_PLAYER_CURSOR_FG,
     correct_flag: PaletteElement::new(color::Rgb(0, 255, 0), color::Rgb(255, 255, 255)),
     wrong_flag: PaletteElement::new(color::Rgb(255, 0, 0), color::Rgb(255, 255, 255)),
+    glyphs: ASCII_GLYPHS,
+    accessibility: Accessibility::Off,
 };
 
+/// Turns off bold, underline and reverse, without touching the colors
+pub const ATTR_RESET: &str = "\x1b[22;24;27m";
+
 pub const BG_RESET: color::Bg<color::Reset> = color::Bg(color::Reset);
 pub const FG_RESET: color::Fg<color::Reset> = color::Fg(color::Reset);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn true_color_and_indexed_colors_are_kept() {
+        assert_eq!(
+            Color::Rgb(1, 2, 3).quantized(ColorMode::TrueColor),
+            Color::Rgb(1, 2, 3)
+        );
+        assert_eq!(
+            Color::Ansi256(42).quantized(ColorMode::Ansi16),
+            Color::Ansi256(42)
+        );
+        assert_eq!(
+            Color::Rgb(1, 2, 3).quantized(ColorMode::Monochrome),
+            Color::None
+        );
+    }
+
+    #[test]
+    fn colors_of_the_cube_map_to_themselves() {
+        for (ri, &r) in CUBE_LEVELS.iter().enumerate() {
+            for (gi, &g) in CUBE_LEVELS.iter().enumerate() {
+                for (bi, &b) in CUBE_LEVELS.iter().enumerate() {
+                    assert_eq!(
+                        Color::Rgb(r, g, b).quantized(ColorMode::Ansi256),
+                        Color::Ansi256((16 + 36 * ri + 6 * gi + bi) as u8)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn grays_use_the_grayscale_ramp() {
+        for idx in 0..24 {
+            let gray = 8 + idx * 10;
+            assert_eq!(
+                Color::Rgb(gray, gray, gray).quantized(ColorMode::Ansi256),
+                Color::Ansi256(232 + idx)
+            );
+        }
+        // Slightly off grays are still closer to the ramp than to the cube
+        assert_eq!(
+            Color::Rgb(130, 128, 126).quantized(ColorMode::Ansi256),
+            Color::Ansi256(244)
+        );
+    }
+
+    #[test]
+    fn basic_colors_are_the_closest_of_xterm() {
+        let ansi16 = |r, g, b| Color::Rgb(r, g, b).quantized(ColorMode::Ansi16);
+        assert_eq!(ansi16(0, 0, 0), Color::Ansi16(0));
+        assert_eq!(ansi16(230, 230, 230), Color::Ansi16(7));
+        assert_eq!(ansi16(200, 10, 10), Color::Ansi16(1));
+        assert_eq!(ansi16(250, 20, 20), Color::Ansi16(9));
+        assert_eq!(ansi16(80, 80, 250), Color::Ansi16(12));
+        assert_eq!(ansi16(250, 250, 250), Color::Ansi16(15));
+    }
+
+    #[test]
+    fn quantized_palettes_only_use_the_colors_of_the_mode() {
+        let palette = MNSWPR_PALETTE.quantized(ColorMode::Ansi16);
+        let colors = [palette.closed.fg.0, palette.cursor_fg.0]
+            .into_iter()
+            .chain(palette.neighbour_count_to_fg_color.iter().map(|fg| fg.0))
+            .chain([
+                palette.open_bg.0,
+                palette.mine.bg.0,
+                palette.wrong_flag.bg.0,
+            ]);
+        for color in colors {
+            assert!(matches!(color, Color::Ansi16(0..=15)), "{color:?}");
+        }
+    }
+}
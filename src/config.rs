@@ -1,12 +1,12 @@
 use crate::colors::{Palette, MNSWPR_PALETTE, OG_PALETTE};
+use crate::theme;
 use std::fmt::Display;
-use std::fs;
 use std::path::Path;
 use std::str::FromStr;
 
 use anyhow::{Context, Result};
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub enum Theme {
     Mnswpr,
     OG,
@@ -18,11 +18,21 @@ impl Theme {
         match self {
             Theme::Mnswpr => Ok(MNSWPR_PALETTE),
             Theme::OG => Ok(OG_PALETTE),
-            Theme::Custom(path) => {
-                let theme_data = fs::read_to_string(path).context("Could not read theme data.")?;
-                let custom_palette = serde_yaml::from_str(&theme_data)?;
-                Ok(custom_palette)
-            }
+            Theme::Custom(path) => theme::load(Path::new(path)).with_context(|| {
+                format!("Run `mnswpr theme check {path}` to see every problem of the theme")
+            }),
+        }
+    }
+}
+
+impl Theme {
+    /// Returns a short name for the theme: the name of the file without extension for custom themes
+    pub fn name(&self) -> String {
+        match self {
+            Theme::Custom(path) => Path::new(path)
+                .file_stem()
+                .map_or(path.clone(), |stem| stem.to_string_lossy().to_string()),
+            theme => theme.to_string(),
         }
     }
 }
@@ -42,28 +52,32 @@ impl FromStr for Theme {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
-            "mnswpr" => Ok(Self::Mnswpr),
-            "og" => Ok(Self::OG),
-            path => {
-                let path_obj = Path::new(path);
-                if !path_obj.exists() {
-                    return Err(format!(
-                        "Expected one of \"mnswpr\", \"og\", or a custom theme path. Custom theme file at path \"{path}\" not found."
-                    ));
-                }
-                if !path_obj.is_file() {
-                    return Err(format!(
-                        "The provided custom theme path (\"{path}\") is not a file."
-                    ));
-                } else {
-                    Ok(Self::Custom(path.to_string()))
-                }
-            }
+            "mnswpr" => return Ok(Self::Mnswpr),
+            "og" => return Ok(Self::OG),
+            _ => {}
+        }
+        if let Some(path) = theme::find(s) {
+            return Ok(Self::Custom(path.to_string_lossy().to_string()));
+        }
+
+        let path_obj = Path::new(s);
+        if !path_obj.exists() {
+            return Err(format!(
+                "Expected one of \"mnswpr\", \"og\", the name of a theme in {}, or a custom theme path. Custom theme file at path \"{s}\" not found.",
+                theme::themes_dir().map_or("$XDG_CONFIG_HOME/mnswpr/themes".to_string(), |dir| dir.display().to_string())
+            ));
+        }
+        if !path_obj.is_file() {
+            Err(format!(
+                "The provided custom theme path (\"{s}\") is not a file."
+            ))
+        } else {
+            Ok(Self::Custom(s.to_string()))
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub enum SizePreset {
     Tiny,
     Small,
@@ -114,3 +128,67 @@ impl FromStr for SizePreset {
         }
     }
 }
+
+/// Whether the number of flags is limited to the number of mines
+#[derive(Copy, Clone, PartialEq)]
+pub enum FlagLimit {
+    On,
+    Off,
+}
+
+impl Display for FlagLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlagLimit::On => write!(f, "on"),
+            FlagLimit::Off => write!(f, "off"),
+        }
+    }
+}
+
+impl FromStr for FlagLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "on" => Ok(FlagLimit::On),
+            "off" => Ok(FlagLimit::Off),
+            v => Err(format!("Expected one of \"on\", \"off\". Got \"{v}\"")),
+        }
+    }
+}
+
+/// What chording on a number does
+#[derive(Copy, Clone, PartialEq)]
+pub enum ChordMode {
+    /// Opens the closed neighbours when as many of them are flagged as the number, leaving the questioned ones closed
+    Classic,
+    /// Like `All`, and flags the closed neighbours when they are exactly as many as the mines left around the number
+    Assisted,
+    /// Opens every neighbour that is not flagged, questioned ones included, when the flags match the number
+    All,
+}
+
+impl Display for ChordMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChordMode::Classic => write!(f, "classic"),
+            ChordMode::Assisted => write!(f, "assisted"),
+            ChordMode::All => write!(f, "all"),
+        }
+    }
+}
+
+impl FromStr for ChordMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "classic" => Ok(ChordMode::Classic),
+            "assisted" => Ok(ChordMode::Assisted),
+            "all" => Ok(ChordMode::All),
+            v => Err(format!(
+                "Expected one of \"classic\", \"assisted\", \"all\". Got \"{v}\""
+            )),
+        }
+    }
+}
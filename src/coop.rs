@@ -0,0 +1,371 @@
+//! Several players clearing the same board together over TCP: one runs `mnswpr coop host`
+//! and the others `mnswpr coop join <addr>`, at any time during the game.
+//!
+//! Only the board of the host counts. The other players send their moves to the host, which applies them
+//! in the order they arrive and sends every applied move to everyone, its own ones included.
+//! Since every player applies the same moves in the same order, a mine ends the game for all of them at once
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::net::{self, Connection, Listener};
+use crate::replay::{Event, Replay};
+
+/// The number of the host. The other players are numbered from 1 in the order they joined
+const HOST: usize = 0;
+
+/// What a player sends to the host
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum ToHost {
+    /// A move the player wants to make
+    Move {
+        event: Event,
+    },
+    Cursor {
+        row: usize,
+        col: usize,
+    },
+}
+
+/// What the host sends to the other players
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum ToPlayer {
+    /// Sent once to a player that joins: their number, and the board with every move made so far
+    Welcome {
+        player: usize,
+        replay: Replay,
+    },
+    Joined {
+        player: usize,
+    },
+    /// A move that was applied to the board
+    Move {
+        player: usize,
+        event: Event,
+    },
+    Cursor {
+        player: usize,
+        row: usize,
+        col: usize,
+    },
+    Left {
+        player: usize,
+    },
+}
+
+/// A player taking part in the game, as seen by the others
+struct Player {
+    /// None until the player moves their cursor for the first time
+    cursor: Option<(usize, usize)>,
+}
+
+enum Role {
+    Host {
+        listener: Listener,
+        /// The connections to the other players, by player number. None for the host and for players that left
+        connections: Vec<Option<Connection<ToHost>>>,
+    },
+    Guest {
+        connection: Connection<ToPlayer>,
+        /// Whether the connection to the host was lost
+        host_left: bool,
+    },
+}
+
+pub struct Coop {
+    role: Role,
+    /// The number of this player
+    player: usize,
+    /// Every player that is still in the game, by player number, including this one
+    players: Vec<Option<Player>>,
+    /// The last cursor position shared with the others
+    shared_cursor: Option<(usize, usize)>,
+    /// The player that uncovered a mine, ending the game
+    mine_uncovered_by: Option<usize>,
+}
+
+impl Coop {
+    /// Starts accepting players on `port`. They can join while the game is being played
+    pub fn host(port: u16) -> Result<Self> {
+        let listener = Listener::bind(&port.to_string())?;
+        Ok(Self {
+            role: Role::Host {
+                listener,
+                connections: vec![None],
+            },
+            player: HOST,
+            players: vec![Some(Player { cursor: None })],
+            shared_cursor: None,
+            mine_uncovered_by: None,
+        })
+    }
+
+    /// Joins the game hosted at `addr`. Returns the board with every move made so far
+    pub fn join(addr: &str) -> Result<(Self, Replay)> {
+        let connection = Connection::new(net::connect(addr)?)?;
+        let ToPlayer::Welcome { player, replay } = connection.receive()? else {
+            bail!("The host did not send the board");
+        };
+
+        // The host then tells who else is playing
+        let mut players: Vec<Option<Player>> = (0..=player).map(|_| None).collect();
+        players[HOST] = Some(Player { cursor: None });
+        players[player] = Some(Player { cursor: None });
+        let coop = Self {
+            role: Role::Guest {
+                connection,
+                host_left: false,
+            },
+            player,
+            players,
+            shared_cursor: None,
+            mine_uncovered_by: None,
+        };
+        Ok((coop, replay))
+    }
+
+    /// Returns the number of this player
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    /// Returns the number of players in the game
+    pub fn player_count(&self) -> usize {
+        self.players.iter().flatten().count()
+    }
+
+    /// Returns the player that uncovered a mine, if the game ended that way
+    pub fn mine_uncovered_by(&self) -> Option<usize> {
+        self.mine_uncovered_by
+    }
+
+    /// Records that `player` uncovered a mine, unless another player already did
+    pub fn mine_uncovered(&mut self, player: usize) {
+        self.mine_uncovered_by.get_or_insert(player);
+    }
+
+    /// Returns whether this player is a guest that lost the connection to the host
+    pub fn host_left(&self) -> bool {
+        matches!(
+            self.role,
+            Role::Guest {
+                host_left: true,
+                ..
+            }
+        )
+    }
+
+    /// Returns the cursors of the other players, with their numbers
+    pub fn cursors(&self) -> impl Iterator<Item = (usize, (usize, usize))> + '_ {
+        self.players
+            .iter()
+            .enumerate()
+            .filter(|(number, _)| *number != self.player)
+            .filter_map(|(number, player)| Some((number, player.as_ref()?.cursor?)))
+    }
+
+    /// Shares a move of this player. Returns whether it can be applied right away, which only the host can do:
+    /// the moves of the other players are applied when the host sends them back
+    pub fn share_move(&mut self, event: Event) -> bool {
+        match &mut self.role {
+            Role::Host { .. } => {
+                self.broadcast(&ToPlayer::Move {
+                    player: HOST,
+                    event,
+                });
+                true
+            }
+            Role::Guest { connection, .. } => {
+                // A lost connection is noticed by the next `poll`
+                let _ = connection.send(&ToHost::Move { event });
+                false
+            }
+        }
+    }
+
+    /// Shares the cursor of this player, if it moved since the last time
+    pub fn share_cursor(&mut self, row: usize, col: usize) {
+        if self.shared_cursor == Some((row, col)) {
+            return;
+        }
+        self.shared_cursor = Some((row, col));
+        match &mut self.role {
+            Role::Host { .. } => {
+                // Kept with the others, for the players that join later
+                if let Some(host) = &mut self.players[HOST] {
+                    host.cursor = Some((row, col));
+                }
+                self.broadcast(&ToPlayer::Cursor {
+                    player: HOST,
+                    row,
+                    col,
+                });
+            }
+            Role::Guest { connection, .. } => {
+                let _ = connection.send(&ToHost::Cursor { row, col });
+            }
+        }
+    }
+
+    /// Handles what happened since the last call: players joining and leaving, cursors and moves.
+    /// `replay` is the game so far, sent to the players that join.
+    /// Returns the moves to apply to the board, in order, with the number of the player that made them
+    pub fn poll(&mut self, replay: &Replay) -> Vec<(usize, Event)> {
+        match self.role {
+            Role::Host { .. } => {
+                self.accept(replay);
+                self.poll_players()
+            }
+            Role::Guest { .. } => self.poll_host(),
+        }
+    }
+
+    /// Welcomes the players that are waiting to join
+    fn accept(&mut self, replay: &Replay) {
+        let Role::Host {
+            listener,
+            connections,
+        } = &mut self.role
+        else {
+            return;
+        };
+
+        loop {
+            let Ok(Some(stream)) = listener.accept() else {
+                return;
+            };
+            let Ok(mut connection) = Connection::new(stream) else {
+                continue;
+            };
+
+            let player = connections.len();
+            let welcome = ToPlayer::Welcome {
+                player,
+                replay: replay.clone(),
+            };
+            if connection.send(&welcome).is_err() {
+                continue;
+            }
+            // The new player learns who is playing and where their cursors are, and the others learn about them
+            for (number, other) in self.players.iter().enumerate() {
+                let Some(other) = other else {
+                    continue;
+                };
+                let _ = connection.send(&ToPlayer::Joined { player: number });
+                if let Some((row, col)) = other.cursor {
+                    let _ = connection.send(&ToPlayer::Cursor {
+                        player: number,
+                        row,
+                        col,
+                    });
+                }
+            }
+            for other in connections.iter_mut().flatten() {
+                let _ = other.send(&ToPlayer::Joined { player });
+            }
+            connections.push(Some(connection));
+            self.players.push(Some(Player { cursor: None }));
+        }
+    }
+
+    fn poll_players(&mut self) -> Vec<(usize, Event)> {
+        let Role::Host { connections, .. } = &mut self.role else {
+            return Vec::new();
+        };
+
+        let mut received = Vec::new();
+        let mut left = Vec::new();
+        for (number, connection) in connections.iter_mut().enumerate() {
+            let Some(conn) = connection else {
+                continue;
+            };
+            loop {
+                match conn.try_receive() {
+                    Ok(Some(message)) => received.push((number, message)),
+                    Ok(None) => break,
+                    Err(_) => {
+                        *connection = None;
+                        left.push(number);
+                        break;
+                    }
+                }
+            }
+        }
+
+        for player in left {
+            self.players[player] = None;
+            self.broadcast(&ToPlayer::Left { player });
+        }
+        let mut moves = Vec::new();
+        for (player, message) in received {
+            match message {
+                ToHost::Move { event } => {
+                    self.broadcast(&ToPlayer::Move { player, event });
+                    moves.push((player, event));
+                }
+                ToHost::Cursor { row, col } => {
+                    if let Some(Some(p)) = self.players.get_mut(player) {
+                        p.cursor = Some((row, col));
+                    }
+                    self.broadcast(&ToPlayer::Cursor { player, row, col });
+                }
+            }
+        }
+        moves
+    }
+
+    fn poll_host(&mut self) -> Vec<(usize, Event)> {
+        let Role::Guest {
+            connection,
+            host_left,
+        } = &mut self.role
+        else {
+            return Vec::new();
+        };
+
+        let mut moves = Vec::new();
+        loop {
+            let message = match connection.try_receive() {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(_) => {
+                    *host_left = true;
+                    break;
+                }
+            };
+            match message {
+                ToPlayer::Move { player, event } => moves.push((player, event)),
+                ToPlayer::Joined { player } => {
+                    if self.players.len() <= player {
+                        self.players.resize_with(player + 1, || None);
+                    }
+                    self.players[player].get_or_insert(Player { cursor: None });
+                }
+                ToPlayer::Cursor { player, row, col } => {
+                    if let Some(Some(p)) = self.players.get_mut(player) {
+                        p.cursor = Some((row, col));
+                    }
+                }
+                ToPlayer::Left { player } => {
+                    if let Some(p) = self.players.get_mut(player) {
+                        *p = None;
+                    }
+                }
+                ToPlayer::Welcome { .. } => {}
+            }
+        }
+        moves
+    }
+
+    /// Sends `message` to every player but the host, without waiting for them to read it.
+    /// Players whose connection was lost, or that stopped reading and got disconnected, are noticed by the next `poll`
+    fn broadcast(&mut self, message: &ToPlayer) {
+        if let Role::Host { connections, .. } = &mut self.role {
+            for connection in connections.iter_mut().flatten() {
+                let _ = connection.send(message);
+            }
+        }
+    }
+}
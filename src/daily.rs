@@ -0,0 +1,249 @@
+//! The daily challenge: one board a day, generated from the date so that everyone plays the same board that day.
+//! Only the first attempt of the day is scored, and it can be shared as a short text summary
+
+use std::fmt::{self, Display};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::config::SizePreset;
+use crate::replay::Replay;
+use crate::stats::{AssistLevel, DailyRecord, GameResult};
+
+/// The size of every daily board
+pub const PRESET: SizePreset = SizePreset::Small;
+
+pub const MINE_PERCENTAGE: u8 = 20;
+
+/// The number of squares of the progress bar of the summary
+const BAR_WIDTH: usize = 10;
+
+/// The daily boards are generated from this and the date, as a whole 32 byte seed. `--seed` only gives 8 bytes
+/// that get stretched, so no seed given to the other modes generates the board of a day in advance
+const SEED_SALT: [u8; 24] = *b"mnswpr daily challenge!!";
+
+/// A day of the calendar, in UTC so that players in different time zones get the same board
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Date {
+    year: i64,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    pub fn today() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self::from_days(seconds as i64 / 86400)
+    }
+
+    /// Returns the date `days` days after 1970-01-01
+    fn from_days(days: i64) -> Self {
+        // The year is counted from March, so that the leap day is the last day of the year
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+        let month = if month_from_march < 10 {
+            month_from_march + 3
+        } else {
+            month_from_march - 9
+        } as u32;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Self { year, month, day }
+    }
+
+    /// Returns the random numbers the board of this day is generated from.
+    /// This is the algorithm behind `StdRng` in rand 0.8, named so that a new version of rand,
+    /// which may pick another one, does not change the board of every day
+    fn rng(&self) -> ChaCha12Rng {
+        let number = self.year as u64 * 10000 + self.month as u64 * 100 + self.day as u64;
+        let mut seed = [0; 32];
+        seed[..SEED_SALT.len()].copy_from_slice(&SEED_SALT);
+        seed[SEED_SALT.len()..].copy_from_slice(&number.to_le_bytes());
+        ChaCha12Rng::from_seed(seed)
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Returns the board of the challenge of `date`, with the area around its center open
+pub fn board(date: Date) -> Replay {
+    let (cols, rows) = PRESET.to_size();
+    Replay::seeded(
+        &mut date.rng(),
+        rows as usize,
+        cols as usize,
+        MINE_PERCENTAGE,
+        false,
+    )
+}
+
+/// Returns the result of a daily challenge as a few lines of text that can be pasted anywhere
+pub fn summary(record: &DailyRecord) -> String {
+    let result = match record.result {
+        GameResult::Won => "Cleared",
+        GameResult::Lost => "Hit a mine",
+        GameResult::Abandoned => "Gave up",
+    };
+    let seconds = record.seconds.round() as u64;
+    let percentage = record.open * 100 / record.empty.max(1);
+
+    // A lost game never shows a full bar, and ends with the mine
+    let bar = if record.result == GameResult::Won {
+        "🟩".repeat(BAR_WIDTH)
+    } else {
+        let filled = (record.open * BAR_WIDTH / record.empty.max(1)).min(BAR_WIDTH - 1);
+        let end = if record.result == GameResult::Lost {
+            "💥"
+        } else {
+            "⬜"
+        };
+        format!(
+            "{}{end}{}",
+            "🟩".repeat(filled),
+            "⬜".repeat(BAR_WIDTH - 1 - filled)
+        )
+    };
+
+    let mut summary = format!(
+        "mnswpr daily {}\n{result} in {}:{:02}, {percentage}% of the board open\n{bar}",
+        record.date,
+        seconds / 60,
+        seconds % 60
+    );
+    if record.assist != AssistLevel::None {
+        summary.push_str(&format!(" ({})", record.assist));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_since_the_epoch_become_dates() {
+        let cases = [
+            (0, "1970-01-01"),
+            (-1, "1969-12-31"),
+            (59, "1970-03-01"),
+            (11016, "2000-02-29"),
+            (19782, "2024-02-29"),
+            (20744, "2026-10-18"),
+            // 2100 is not a leap year
+            (47540, "2100-02-28"),
+            (47541, "2100-03-01"),
+        ];
+        for (days, date) in cases {
+            assert_eq!(Date::from_days(days).to_string(), date, "{days} days");
+        }
+    }
+
+    /// Draws the mines of a board as `x`, and the other cells as `-`
+    fn mine_map(replay: &Replay) -> Vec<String> {
+        let mut map = vec![vec!['-'; replay.cols]; replay.rows];
+        for &(row, col) in &replay.mines {
+            map[row][col] = 'x';
+        }
+        map.into_iter().map(String::from_iter).collect()
+    }
+
+    #[test]
+    fn the_board_of_a_day_never_changes() {
+        // Everyone who plays on this day must get this board, whatever version of mnswpr and rand they run
+        let expected = [
+            "--x-xx-x-------x--x-----------",
+            "--x-xx--x--------x-x------x---",
+            "------------x-x--x--x-------x-",
+            "x-------------x-----x---------",
+            "------x-xx---x--xx-------x-x--",
+            "-----x-------------xx-x-x----x",
+            "-x-xx---x--xx--------x----x---",
+            "---x-----x--x-----------------",
+            "---------------x------------x-",
+            "x---------xx------x------x-x--",
+            "--x--x---xx-x-------------x---",
+            "-----xx----------x------x-xx--",
+            "----------x--------x------x--x",
+            "-x------x-x-----x-xx----------",
+            "----x---x---xx--x--x----xxx--x",
+            "x----x--x-----x-------x-------",
+            "------------x---x-------x-----",
+            "xx---xx------x-------x--------",
+            "x---xx------x-------x---xxxx--",
+            "----x------------x--------xx--",
+        ];
+        assert_eq!(mine_map(&board(Date::from_days(20744))), expected);
+    }
+
+    #[test]
+    fn each_day_gets_its_own_board() {
+        assert_ne!(
+            board(Date::from_days(20744)).mines,
+            board(Date::from_days(20745)).mines
+        );
+    }
+
+    #[test]
+    fn summaries_show_the_result_time_and_progress() {
+        let won = DailyRecord {
+            date: String::from("2026-10-18"),
+            result: GameResult::Won,
+            open: 400,
+            empty: 400,
+            seconds: 83.4,
+            assist: AssistLevel::None,
+        };
+        assert_eq!(
+            summary(&won),
+            "mnswpr daily 2026-10-18\nCleared in 1:23, 100% of the board open\n🟩🟩🟩🟩🟩🟩🟩🟩🟩🟩"
+        );
+
+        let lost = DailyRecord {
+            result: GameResult::Lost,
+            open: 180,
+            seconds: 59.6,
+            ..won
+        };
+        assert_eq!(
+            summary(&lost),
+            "mnswpr daily 2026-10-18\nHit a mine in 1:00, 45% of the board open\n🟩🟩🟩🟩💥⬜⬜⬜⬜⬜"
+        );
+
+        let abandoned = DailyRecord {
+            result: GameResult::Abandoned,
+            open: 0,
+            seconds: 0.0,
+            assist: AssistLevel::Cursor,
+            ..lost
+        };
+        assert_eq!(
+            summary(&abandoned),
+            "mnswpr daily 2026-10-18\nGave up in 0:00, 0% of the board open\n⬜⬜⬜⬜⬜⬜⬜⬜⬜⬜ (assisted)"
+        );
+    }
+
+    #[test]
+    fn a_lost_game_never_fills_the_bar() {
+        let record = DailyRecord {
+            date: String::from("2026-10-18"),
+            result: GameResult::Lost,
+            open: 399,
+            empty: 400,
+            seconds: 1.0,
+            assist: AssistLevel::None,
+        };
+        assert!(summary(&record).ends_with("🟩🟩🟩🟩🟩🟩🟩🟩🟩💥"));
+    }
+}
@@ -0,0 +1,238 @@
+//! Export of finished boards to files that can be shared, drawn the way the end screen shows them
+
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use unicode_width::UnicodeWidthStr;
+
+use crate::cell::Look;
+use crate::colors::{Palette, BG_RESET, FG_RESET};
+use crate::field::Field;
+
+/// The colors used where the palette has none, e.g. in no-color mode
+const DEFAULT_BG: (u8, u8, u8) = (0, 0, 0);
+const DEFAULT_FG: (u8, u8, u8) = (229, 229, 229);
+
+/// The size in pixels of a terminal column and of a line in the svg export
+const CHAR_WIDTH: usize = 10;
+const LINE_HEIGHT: usize = 20;
+
+#[derive(Copy, Clone, Debug)]
+pub enum ExportFormat {
+    Svg,
+    /// A standalone html page
+    Html,
+    /// Text with the escape sequences of the terminal, that can be shown with `cat`
+    Ansi,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Svg => "svg",
+            ExportFormat::Html => "html",
+            ExportFormat::Ansi => "txt",
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ExportFormat::Svg => "svg",
+            ExportFormat::Html => "html",
+            ExportFormat::Ansi => "ansi",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "svg" => Ok(ExportFormat::Svg),
+            "html" => Ok(ExportFormat::Html),
+            "ansi" => Ok(ExportFormat::Ansi),
+            v => Err(format!(
+                "Expected one of \"svg\", \"html\", \"ansi\". Got \"{v}\""
+            )),
+        }
+    }
+}
+
+/// Returns the board drawn in `format`, below a line with `title`
+pub fn render(format: ExportFormat, field: &Field, palette: &Palette, title: &str) -> String {
+    match format {
+        ExportFormat::Svg => svg(field, palette, title),
+        ExportFormat::Html => html(field, palette, title),
+        ExportFormat::Ansi => ansi(field, palette, title),
+    }
+}
+
+/// Writes the board to a new file in the current directory, named after the current time, and returns its path
+pub fn save(
+    format: ExportFormat,
+    field: &Field,
+    palette: &Palette,
+    title: &str,
+) -> Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    let mut path = PathBuf::from(format!("mnswpr-{timestamp}.{}", format.extension()));
+    let mut copy = 1;
+    while path.exists() {
+        path = PathBuf::from(format!("mnswpr-{timestamp}-{copy}.{}", format.extension()));
+        copy += 1;
+    }
+
+    fs::write(&path, render(format, field, palette, title))
+        .context("Could not write the exported board.")?;
+    Ok(path)
+}
+
+fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Returns the background and the text color of `look`, as hex colors
+fn colors(look: &Look) -> (String, String) {
+    let bg = hex(look.bg.0.to_rgb().unwrap_or(DEFAULT_BG));
+    let fg = hex(look.fg.0.to_rgb().unwrap_or(DEFAULT_FG));
+    if look.invert {
+        (fg, bg)
+    } else {
+        (bg, fg)
+    }
+}
+
+/// Escapes the characters that have a meaning in xml and html
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn svg(field: &Field, palette: &Palette, title: &str) -> String {
+    let cell_width = palette.glyphs.cell_width() * CHAR_WIDTH;
+    let width = (field.cols * cell_width).max(title.width() * CHAR_WIDTH);
+    let height = (field.rows + 1) * LINE_HEIGHT;
+
+    let mut s = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" font-family=\"monospace\" font-size=\"16\">\n"
+    );
+    s.push_str(&format!(
+        "<rect width=\"{width}\" height=\"{height}\" fill=\"{}\"/>\n",
+        hex(DEFAULT_BG)
+    ));
+    s.push_str(&format!(
+        "<text x=\"0\" y=\"{}\" fill=\"{}\" xml:space=\"preserve\">{}</text>\n",
+        LINE_HEIGHT - 5,
+        hex(DEFAULT_FG),
+        escape(title)
+    ));
+
+    for row in 0..field.rows {
+        for col in 0..field.cols {
+            let look = field.get_unchecked(row, col).look_lost(palette);
+            let (bg, fg) = colors(&look);
+            let (x, y) = (col * cell_width, (row + 1) * LINE_HEIGHT);
+
+            s.push_str(&format!(
+                "<rect x=\"{x}\" y=\"{y}\" width=\"{cell_width}\" height=\"{LINE_HEIGHT}\" fill=\"{bg}\"/>\n"
+            ));
+            let glyph = look.repr.trim();
+            if glyph.is_empty() {
+                continue;
+            }
+            s.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" fill=\"{fg}\" text-anchor=\"middle\"{}{}>{}</text>\n",
+                x + cell_width / 2,
+                y + LINE_HEIGHT - 5,
+                if look.bold {
+                    " font-weight=\"bold\""
+                } else {
+                    ""
+                },
+                if look.underline {
+                    " text-decoration=\"underline\""
+                } else {
+                    ""
+                },
+                escape(glyph)
+            ));
+        }
+    }
+    s.push_str("</svg>\n");
+    s
+}
+
+fn html(field: &Field, palette: &Palette, title: &str) -> String {
+    let glyphs = &palette.glyphs;
+    let left = " ".repeat(glyphs.cursor_left.width());
+    let right = " ".repeat(glyphs.cursor_right.width());
+
+    let mut board = String::new();
+    for row in 0..field.rows {
+        for col in 0..field.cols {
+            let look = field.get_unchecked(row, col).look_lost(palette);
+            let (bg, fg) = colors(&look);
+
+            let mut style = format!("background:{bg};color:{fg}");
+            if look.bold {
+                style.push_str(";font-weight:bold");
+            }
+            if look.underline {
+                style.push_str(";text-decoration:underline");
+            }
+            board.push_str(&format!(
+                "<span style=\"{style}\">{left}{}{right}</span>",
+                escape(&look.repr)
+            ));
+        }
+        board.push('\n');
+    }
+
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>mnswpr</title>
+<style>
+body {{ background: {bg}; color: {fg}; }}
+pre {{ font-family: monospace; line-height: 1.2; }}
+</style>
+</head>
+<body>
+<pre>{title}
+{board}</pre>
+</body>
+</html>
+",
+        bg = hex(DEFAULT_BG),
+        fg = hex(DEFAULT_FG),
+        title = escape(title),
+    )
+}
+
+fn ansi(field: &Field, palette: &Palette, title: &str) -> String {
+    let mut s = format!("{title}\n");
+    for row in 0..field.rows {
+        for col in 0..field.cols {
+            let cell = field.get_unchecked(row, col);
+            s.push_str(&cell.to_string_with_palette_lost(palette, false));
+        }
+        s.push_str(&format!("{BG_RESET}{FG_RESET}\n"));
+    }
+    s
+}
@@ -2,6 +2,7 @@ use rand::{thread_rng, Rng};
 
 use crate::cell;
 
+#[derive(Clone)]
 pub struct Field {
     pub rows: usize,
     pub cols: usize,
@@ -9,6 +10,8 @@ pub struct Field {
     pub closed_empty_cells: usize,
     pub mine_count: usize,
     pub flag_count: usize,
+    /// If active, more flags than mines can be placed
+    pub unlimited_flags: bool,
 }
 
 impl Field {
@@ -25,6 +28,7 @@ impl Field {
             closed_empty_cells: rows * cols,
             mine_count: 0,
             flag_count: 0,
+            unlimited_flags: false,
         }
     }
 
@@ -36,6 +40,15 @@ impl Field {
         self.flag_count = 0;
     }
 
+    /// Closes every cell and removes the flags, keeping the mines where they are
+    pub fn restart(&mut self) {
+        for cell in &mut self.grid {
+            cell.set_state(cell::State::Closed);
+        }
+        self.closed_empty_cells = self.rows * self.cols - self.mine_count;
+        self.flag_count = 0;
+    }
+
     /// Returns the tuple (row, col) corresponding to the index passed as input
     /// Does not check whether the resulting position is out of bounds
     fn idx_to_position(&self, idx: usize) -> (usize, usize) {
@@ -51,29 +64,40 @@ impl Field {
     }
 
     /// Randomizes the content of the field keeping a safe area of 1 tile around the cursor
-    pub fn randomize(&mut self, mut mine_percentage: u8, current_row: usize, current_col: usize) {
+    pub fn randomize(&mut self, mine_percentage: u8, current_row: usize, current_col: usize) {
+        self.randomize_with(&mut thread_rng(), mine_percentage, current_row, current_col);
+    }
+
+    /// Same as `randomize`, but takes the random numbers from `rng`, so that a seeded `rng` always gives the same field.
+    /// Every cell gets a mine or not from `rng` wherever the safe area is, and the mines that fall in it
+    /// are then moved to other empty cells, so that the safe area only changes the cells it moves the mines to
+    pub fn randomize_with(
+        &mut self,
+        rng: &mut impl Rng,
+        mut mine_percentage: u8,
+        current_row: usize,
+        current_col: usize,
+    ) {
         if mine_percentage >= 100 {
             mine_percentage = 99;
         }
 
-        let mut closed_empty_cells = 0;
-        let mut mine_count = 0;
+        let is_safe =
+            |row: usize, col: usize| row.abs_diff(current_row) < 2 && col.abs_diff(current_col) < 2;
+        let mut displaced_mines = 0;
 
         // Generate random board
-        let mut rng = thread_rng();
         for idx in 0..self.rows * self.cols {
             let (row, col) = self.idx_to_position(idx);
 
             let cell_content = if rng.gen_range(1..=100) <= mine_percentage {
-                if row.abs_diff(current_row) < 2 && col.abs_diff(current_col) < 2 {
-                    closed_empty_cells += 1;
+                if is_safe(row, col) {
+                    displaced_mines += 1;
                     cell::Content::Empty
                 } else {
-                    mine_count += 1;
                     cell::Content::Mine
                 }
             } else {
-                closed_empty_cells += 1;
                 cell::Content::Empty
             };
 
@@ -84,12 +108,113 @@ impl Field {
             };
         }
 
-        self.closed_empty_cells = closed_empty_cells;
-        self.mine_count = mine_count;
+        // Moves the mines out of the safe area, unless the rest of the field is already full of mines
+        let mut free_cells: Vec<usize> = (0..self.rows * self.cols)
+            .filter(|&idx| {
+                let (row, col) = self.idx_to_position(idx);
+                !self.grid[idx].contains_mine() && !is_safe(row, col)
+            })
+            .collect();
+        for _ in 0..displaced_mines.min(free_cells.len()) {
+            let idx = free_cells.swap_remove(rng.gen_range(0..free_cells.len()));
+            self.grid[idx].content = cell::Content::Mine;
+        }
+
+        self.mine_count = self.grid.iter().filter(|cell| cell.contains_mine()).count();
+        self.closed_empty_cells = self.rows * self.cols - self.mine_count;
 
         self.recompute_neighbouroing_counts();
     }
 
+    /// Places mines exactly at the given positions, leaving every cell closed.
+    /// Out of bounds positions are ignored
+    pub fn set_mines(&mut self, mines: &[(usize, usize)]) {
+        self.reset();
+
+        for &(row, col) in mines {
+            if let Some(cell) = self.get_mut(row, col) {
+                if !cell.contains_mine() {
+                    cell.content = cell::Content::Mine;
+                    self.mine_count += 1;
+                    self.closed_empty_cells -= 1;
+                }
+            }
+        }
+
+        self.recompute_neighbouroing_counts();
+    }
+
+    /// Opens the single empty cell at position (row, col) without spreading to its neighbours.
+    /// Returns None if the position is out of bounds or if the cell contains a mine
+    pub fn reveal_at(&mut self, row: usize, col: usize) -> Option<()> {
+        let cell = self.get_mut(row, col)?;
+        if cell.contains_mine() {
+            return None;
+        }
+        if cell.is_closed() {
+            cell.set_state(cell::State::Open);
+            self.closed_empty_cells -= 1;
+        }
+        Some(())
+    }
+
+    /// Returns the positions of the in-bounds neighbours of the cell at position (row, col)
+    pub fn neighbours(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        let mut nbors = Vec::with_capacity(8);
+
+        for drow in -1..=1_isize {
+            for dcol in -1..=1_isize {
+                if drow == 0 && dcol == 0 {
+                    continue;
+                }
+
+                let r = row as isize + drow;
+                let c = col as isize + dcol;
+                if r < 0 || c < 0 || r >= self.rows as isize || c >= self.cols as isize {
+                    continue;
+                }
+                nbors.push((r as usize, c as usize));
+            }
+        }
+        nbors
+    }
+
+    /// Returns the 3BV of the field, that is the minimum number of clicks needed to clear it without flagging
+    pub fn bbbv(&self) -> usize {
+        let mut marked = vec![false; self.rows * self.cols];
+        let mut clicks = 0;
+
+        // Every region of 0s, together with its border, is cleared by a single click
+        for idx in 0..self.rows * self.cols {
+            let cell = &self.grid[idx];
+            if marked[idx] || cell.contains_mine() || cell.neighbouring_bomb_count != 0 {
+                continue;
+            }
+
+            clicks += 1;
+            marked[idx] = true;
+            let mut stack = vec![self.idx_to_position(idx)];
+            while let Some((row, col)) = stack.pop() {
+                for (r, c) in self.neighbours(row, col) {
+                    let nbor_idx = self.position_to_idx(r, c);
+                    if marked[nbor_idx] {
+                        continue;
+                    }
+                    marked[nbor_idx] = true;
+                    if self.grid[nbor_idx].neighbouring_bomb_count == 0 {
+                        stack.push((r, c));
+                    }
+                }
+            }
+        }
+
+        // Every other empty cell needs its own click
+        clicks
+            + (0..self.rows * self.cols)
+                .filter(|&idx| !marked[idx] && !self.grid[idx].contains_mine())
+                .count()
+    }
+
     /// Updates the neighboring bomb count for each cell in the field.
     fn recompute_neighbouroing_counts(&mut self) {
         for idx in 0..self.rows * self.cols {
@@ -223,24 +348,29 @@ impl Field {
         Some(false)
     }
 
-    /// Toggles the state of the cell at position (row, col) and updates `self.flag_count`
+    /// Returns whether one more flag can be placed
+    pub fn can_flag(&self) -> bool {
+        self.unlimited_flags || self.flag_count < self.mine_count
+    }
+
+    /// Moves the cell at position (row, col) to the next state of the cycle closed, flagged, questioned
+    /// and updates `self.flag_count`. Closed cells stay closed if no more flags can be placed.
     /// Returns None if position (row, col) is out of bounds
     pub fn toggle_flag_at(&mut self, row: usize, col: usize) -> Option<()> {
-        let can_flag = self.flag_count < self.mine_count;
+        let can_flag = self.can_flag();
 
         let cell_under_cursor = self.get_mut(row, col)?;
 
         match cell_under_cursor.state {
-            cell::State::Closed => {
-                if can_flag {
-                    cell_under_cursor.set_state(cell::State::Flagged);
-                    self.flag_count += 1;
-                }
+            cell::State::Closed if can_flag => {
+                cell_under_cursor.set_state(cell::State::Flagged);
+                self.flag_count += 1;
             }
             cell::State::Flagged => {
-                cell_under_cursor.set_state(cell::State::Closed);
+                cell_under_cursor.set_state(cell::State::Questioned);
                 self.flag_count -= 1;
             }
+            cell::State::Questioned => cell_under_cursor.set_state(cell::State::Closed),
             _ => {}
         };
 
@@ -279,11 +409,16 @@ impl Field {
         Some(count)
     }
 
-    /// Uncovers the closed cells around the cell at (row, col).
+    /// Uncovers the closed cells around the cell at (row, col), leaving the questioned ones closed if `keep_questioned`.
     /// Returns None if the position (row, col) is out of bounds, otherwise
     /// it returns Some(true) if there was a mine around the current cell (returns as soon as a mine is found),
     /// otherwise returns Some(false).
-    pub fn uncover_around_cell_at(&mut self, row: usize, col: usize) -> Option<bool> {
+    pub fn uncover_around_cell_at(
+        &mut self,
+        row: usize,
+        col: usize,
+        keep_questioned: bool,
+    ) -> Option<bool> {
         // Return early None if (row, col) is out of bounds
         self.get(row, col)?;
 
@@ -303,10 +438,11 @@ impl Field {
                 let c = (col + dcol) as usize;
 
                 if let Some(cell) = self.get(r, c) {
-                    if cell.is_closed() {
-                        if self.uncover_at(r, c).expect("Index out of bounds") {
-                            return Some(true);
-                        }
+                    if cell.is_closed()
+                        && !(keep_questioned && cell.is_questioned())
+                        && self.uncover_at(r, c).expect("Index out of bounds")
+                    {
+                        return Some(true);
                     }
                 }
             }
@@ -366,9 +502,11 @@ impl Field {
                 let r = (row + drow) as usize;
                 let c = (col + dcol) as usize;
 
-                if let Some(cell) = self.get(r, c) {
-                    if cell.is_closed() {
-                        self.toggle_flag_at(r, c);
+                let can_flag = self.can_flag();
+                if let Some(cell) = self.get_mut(r, c) {
+                    if cell.is_closed() && can_flag {
+                        cell.set_state(cell::State::Flagged);
+                        self.flag_count += 1;
                     }
                 }
             }
@@ -377,3 +515,51 @@ impl Field {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn mines(field: &Field) -> Vec<(usize, usize)> {
+        (0..field.rows * field.cols)
+            .map(|idx| field.idx_to_position(idx))
+            .filter(|&(row, col)| field.get_unchecked(row, col).contains_mine())
+            .collect()
+    }
+
+    #[test]
+    fn the_first_cell_opened_only_moves_the_mines_around_it() {
+        let randomized = |row, col| {
+            let mut field = Field::new(16, 16);
+            field.randomize_with(&mut StdRng::seed_from_u64(42), 30, row, col);
+            field
+        };
+        let (top_left, bottom_right) = (randomized(0, 0), randomized(15, 15));
+
+        assert_eq!(top_left.mine_count, bottom_right.mine_count);
+        assert_eq!(top_left.mine_count, mines(&top_left).len());
+        assert_eq!(top_left.closed_empty_cells, 256 - top_left.mine_count);
+        // Far from both safe areas of 4 cells, the fields only differ where the top left one moved its mines to
+        let far = |&(row, col): &(usize, usize)| (3..13).contains(&row) && (3..13).contains(&col);
+        let differing = mines(&top_left)
+            .into_iter()
+            .filter(far)
+            .filter(|mine| !mines(&bottom_right).contains(mine))
+            .count();
+        assert!(differing <= 4, "{differing} mines moved");
+        for (row, col) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            assert!(!top_left.get_unchecked(row, col).contains_mine());
+        }
+    }
+
+    #[test]
+    fn mines_stay_out_of_the_safe_area_of_a_full_field() {
+        let mut field = Field::new(3, 3);
+        field.randomize_with(&mut StdRng::seed_from_u64(1), 99, 1, 1);
+        assert_eq!(field.mine_count, 0);
+        assert_eq!(field.closed_empty_cells, 9);
+    }
+}
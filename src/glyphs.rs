@@ -0,0 +1,192 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use unicode_width::UnicodeWidthStr;
+
+/// The built-in glyph sets
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GlyphSet {
+    Ascii,
+    Unicode,
+    /// Needs a font patched with the Nerd Fonts icons
+    NerdFont,
+}
+
+impl GlyphSet {
+    pub fn glyphs(&self) -> Glyphs {
+        match self {
+            GlyphSet::Ascii => ASCII_GLYPHS,
+            GlyphSet::Unicode => UNICODE_GLYPHS,
+            GlyphSet::NerdFont => NERD_FONT_GLYPHS,
+        }
+    }
+}
+
+impl Display for GlyphSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            GlyphSet::Ascii => "ascii",
+            GlyphSet::Unicode => "unicode",
+            GlyphSet::NerdFont => "nerd-font",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for GlyphSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ascii" => Ok(GlyphSet::Ascii),
+            "unicode" => Ok(GlyphSet::Unicode),
+            "nerd-font" => Ok(GlyphSet::NerdFont),
+            v => Err(format!(
+                "Expected one of \"ascii\", \"unicode\", \"nerd-font\". Got \"{v}\""
+            )),
+        }
+    }
+}
+
+/// The glyphs used to draw the cells. Numbers are always drawn with digits.
+///
+/// In a theme file, `glyphs` is either the name of a built-in set, or a map with any of the fields below
+/// plus an optional `base` set that provides the missing ones
+#[derive(Clone, Debug)]
+pub struct Glyphs {
+    pub closed: Cow<'static, str>,
+    pub flag: Cow<'static, str>,
+    /// The mark of cells the player is not sure about
+    pub questioned: Cow<'static, str>,
+    pub mine: Cow<'static, str>,
+    /// A flag placed on a mine, on the end screen of the accessibility modes
+    pub correct_flag: Cow<'static, str>,
+    /// A flag placed on an empty cell, on the end screen of the accessibility modes
+    pub wrong_flag: Cow<'static, str>,
+    pub cursor_left: Cow<'static, str>,
+    pub cursor_right: Cow<'static, str>,
+}
+
+impl Default for Glyphs {
+    fn default() -> Self {
+        ASCII_GLYPHS
+    }
+}
+
+impl Glyphs {
+    /// Returns the number of terminal columns taken by the content of a cell, that is by its widest glyph
+    pub fn content_width(&self) -> usize {
+        [
+            &self.closed,
+            &self.flag,
+            &self.questioned,
+            &self.mine,
+            &self.correct_flag,
+            &self.wrong_flag,
+        ]
+        .iter()
+        .map(|g| g.width())
+        .max()
+        .unwrap_or(1)
+        .max(1)
+    }
+
+    /// Returns the number of terminal columns taken by a whole cell, cursor included
+    pub fn cell_width(&self) -> usize {
+        self.cursor_left.width() + self.content_width() + self.cursor_right.width()
+    }
+
+    /// Pads `glyph` with spaces so that it takes exactly `content_width()` columns
+    pub fn pad(&self, glyph: &str) -> String {
+        let padding = self.content_width().saturating_sub(glyph.width());
+        format!("{glyph}{}", " ".repeat(padding))
+    }
+}
+
+/// The glyphs as written in a theme file
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum GlyphsDef {
+    Set(GlyphSet),
+    Custom {
+        base: Option<GlyphSet>,
+        closed: Option<String>,
+        flag: Option<String>,
+        questioned: Option<String>,
+        mine: Option<String>,
+        correct_flag: Option<String>,
+        wrong_flag: Option<String>,
+        cursor_left: Option<String>,
+        cursor_right: Option<String>,
+    },
+}
+
+impl GlyphsDef {
+    /// Returns the glyphs, taking the ones that are not given from `base` unless another base set is named
+    pub fn resolve(self, base: Glyphs) -> Glyphs {
+        match self {
+            GlyphsDef::Set(set) => set.glyphs(),
+            GlyphsDef::Custom {
+                base: base_set,
+                closed,
+                flag,
+                questioned,
+                mine,
+                correct_flag,
+                wrong_flag,
+                cursor_left,
+                cursor_right,
+            } => {
+                let base = base_set.map_or(base, |set| set.glyphs());
+                let or_base =
+                    |glyph: Option<String>, base: Cow<'static, str>| glyph.map_or(base, Cow::Owned);
+                Glyphs {
+                    closed: or_base(closed, base.closed),
+                    flag: or_base(flag, base.flag),
+                    questioned: or_base(questioned, base.questioned),
+                    mine: or_base(mine, base.mine),
+                    correct_flag: or_base(correct_flag, base.correct_flag),
+                    wrong_flag: or_base(wrong_flag, base.wrong_flag),
+                    cursor_left: or_base(cursor_left, base.cursor_left),
+                    cursor_right: or_base(cursor_right, base.cursor_right),
+                }
+            }
+        }
+    }
+}
+
+pub const ASCII_GLYPHS: Glyphs = Glyphs {
+    closed: Cow::Borrowed("."),
+    flag: Cow::Borrowed("F"),
+    questioned: Cow::Borrowed("?"),
+    mine: Cow::Borrowed("*"),
+    correct_flag: Cow::Borrowed("F"),
+    wrong_flag: Cow::Borrowed("X"),
+    cursor_left: Cow::Borrowed("["),
+    cursor_right: Cow::Borrowed("]"),
+};
+
+pub const UNICODE_GLYPHS: Glyphs = Glyphs {
+    closed: Cow::Borrowed("▪"),
+    flag: Cow::Borrowed("⚑"),
+    questioned: Cow::Borrowed("?"),
+    mine: Cow::Borrowed("✹"),
+    correct_flag: Cow::Borrowed("⚑"),
+    wrong_flag: Cow::Borrowed("✗"),
+    cursor_left: Cow::Borrowed("["),
+    cursor_right: Cow::Borrowed("]"),
+};
+
+pub const NERD_FONT_GLYPHS: Glyphs = Glyphs {
+    closed: Cow::Borrowed("\u{f0c8}"),
+    flag: Cow::Borrowed("\u{f024}"),
+    questioned: Cow::Borrowed("\u{f128}"),
+    mine: Cow::Borrowed("\u{f1e2}"),
+    correct_flag: Cow::Borrowed("\u{f00c}"),
+    wrong_flag: Cow::Borrowed("\u{f00d}"),
+    cursor_left: Cow::Borrowed("["),
+    cursor_right: Cow::Borrowed("]"),
+};
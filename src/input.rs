@@ -0,0 +1,65 @@
+use std::io::{self, stdin};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use termion::event::{Event, Key};
+use termion::input::TermRead;
+
+/// What waiting for an event ended with
+pub enum Wait {
+    Event(Event),
+    /// Nothing happened before the timeout
+    Timeout,
+    /// Stdin was closed, no more events will come
+    Closed,
+}
+
+/// Reads the events of the terminal on a separate thread, so that waiting for them can time out.
+/// The thread keeps reading stdin until the program exits, so only one `Input` should be created
+pub struct Input {
+    events: Receiver<io::Result<Event>>,
+}
+
+impl Input {
+    pub fn spawn() -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for e in stdin().events() {
+                if tx.send(e).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { events: rx }
+    }
+
+    /// Waits for the next event, for at most `timeout` if given
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<Wait> {
+        let e = match timeout {
+            Some(timeout) => match self.events.recv_timeout(timeout) {
+                Ok(e) => e,
+                Err(RecvTimeoutError::Timeout) => return Ok(Wait::Timeout),
+                Err(RecvTimeoutError::Disconnected) => return Ok(Wait::Closed),
+            },
+            None => match self.events.recv() {
+                Ok(e) => e,
+                Err(_) => return Ok(Wait::Closed),
+            },
+        };
+        Ok(Wait::Event(e?))
+    }
+
+    /// Returns the keys pressed from now on. The iterator ends when stdin gets closed
+    pub fn keys(&self) -> impl Iterator<Item = Result<Key>> + '_ {
+        std::iter::from_fn(|| loop {
+            match self.wait(None) {
+                Ok(Wait::Event(Event::Key(key))) => return Some(Ok(key)),
+                Ok(Wait::Event(_)) => continue,
+                Ok(Wait::Timeout | Wait::Closed) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        })
+    }
+}
@@ -6,101 +6,486 @@
 //     clippy::cargo,
 // )]
 
-use std::io::{stdin, stdout, Write};
+use std::fs;
+use std::io::{stdout, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use clap::{command, Parser};
+use clap::{Parser, Subcommand};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
-use colors::FG_RESET;
-use termion::event::{Event, Key};
-use termion::input::TermRead;
-use termion::raw::IntoRawMode;
+use colors::{Accessibility, ColorMode, Palette, FG_RESET};
+use termion::event::Key;
+use termion::raw::{IntoRawMode, RawTerminal};
 use termion::{color, cursor::HideCursor};
 
+mod bench;
+mod bot;
 mod cell;
 mod colors;
 mod config;
+mod coop;
+mod daily;
+mod export;
 mod field;
+mod glyphs;
+mod input;
+mod menu;
 mod mnswpr;
+mod net;
+mod postmortem;
+mod puzzle;
+mod render;
+mod replay;
+mod save;
+mod solver;
+mod spectate;
+mod stats;
+mod theme;
+mod versus;
 
-use crate::mnswpr::Mnswpr;
+use crate::bot::Engine;
+use crate::coop::Coop;
+use crate::daily::Date;
+use crate::export::ExportFormat;
+use crate::field::Field;
+use crate::glyphs::GlyphSet;
+use crate::input::Input;
+use crate::mnswpr::{Mnswpr, Outcome};
+use crate::puzzle::Puzzle;
+use crate::replay::Replay;
+use crate::save::Save;
+use crate::solver::{DifficultyBand, Technique};
+use crate::spectate::{Message, Spectators, Watch};
+use crate::stats::{DailyRecord, GameRecord, GameResult, Stats};
+use crate::theme::ThemeCycle;
+use crate::versus::{RaceResult, Versus};
 
-use config::{SizePreset, Theme};
+use config::{ChordMode, FlagLimit, SizePreset, Theme};
 
 /// A simple minesweeper game for the terminal.
 ///
 /// Move the cursor with either wasd, hjkl or the arrows.
 ///
-/// Flag/unflag the cell under the cursor by pressing f, or uncover it by pressing <space> or <insert>.
+/// Flag the cell under the cursor by pressing f, press f again to mark it with a question mark and again to clear it.
+/// Uncover it by pressing <space> or <insert>.
 ///
-/// Additionally, if you think you have flagged all the mines around a cell, you can press <space> or <enter> on it to open all
+/// Additionally, if you think you have flagged all the mines around a cell, you can press c on it to open all
 /// of the closed cells around it. Note that this will try to open cells that contain mines!
-#[derive(Parser)]
+///
+/// Press n to abandon the board and play a new one, or r to play the same board again from the start.
+///
+/// Press p to pause: the board is hidden and the timer stops until p is pressed again.
+///
+/// Press t to switch to the next theme, and ? to see every key that can be used in the game.
+///
+/// Run without arguments to choose the settings from a menu.
+#[derive(Parser, Clone)]
 #[command(author, version, about)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// The number of columns of the field. Must be greater than 1.
-    #[arg(short, long="columns", value_parser=clap::value_parser!(u64).range(1..))]
+    #[arg(short, long="columns", global=true, value_parser=clap::value_parser!(u64).range(1..))]
     cols: Option<u64>,
 
     /// The number of rows of the field. Must be greater than 1.
-    #[arg(short, long, value_parser=clap::value_parser!(u64).range(1..))]
+    #[arg(short, long, global=true, value_parser=clap::value_parser!(u64).range(1..))]
     rows: Option<u64>,
 
     /// The percentage of mines in the field. Must be in the range (1, 100).
-    #[arg(short, long, default_value_t=20, value_parser=clap::value_parser!(u8).range(1..100))]
+    #[arg(short, long, global=true, default_value_t=20, value_parser=clap::value_parser!(u8).range(1..100))]
     mine_percentage: u8,
 
     /// The size preset of the field. Note that `-c` and `-r` take precendence over the preset.
-    #[arg(short, long, default_value_t=SizePreset::Tiny)]
+    #[arg(short, long, global=true, default_value_t=SizePreset::Tiny)]
     preset: SizePreset,
 
-    /// The theme of the board
-    #[arg(short, long, default_value_t=Theme::Mnswpr)]
+    /// The theme of the board: "mnswpr", "og", the name of a theme in $XDG_CONFIG_HOME/mnswpr/themes, or the path of a theme file
+    #[arg(short, long, global=true, default_value_t=Theme::Mnswpr)]
     theme: Theme,
 
+    /// The glyphs used to draw the cells: "ascii", "unicode" or "nerd-font".
+    /// If not given, the glyphs of the theme are used
+    #[arg(long, global = true)]
+    glyphs: Option<GlyphSet>,
+
+    /// The colors the terminal can show: "truecolor", "256", "16" or "mono".
+    /// If not given, it is guessed from the NO_COLOR, COLORTERM and TERM environment variables
+    #[arg(long, global = true)]
+    color_mode: Option<ColorMode>,
+
+    /// Tell cells apart by their glyphs and text attributes (bold, underline, reverse) instead of by color only.
+    /// "colorblind" keeps the colors of the theme but avoids telling flags apart by red and green,
+    /// "no-color" does not use colors at all
+    #[arg(long, global = true, default_value_t = Accessibility::Off)]
+    accessibility: Accessibility,
+
     /// If active, trying to flag an open cell with N neighboring mines and N non-open adjacent cells will result in
     /// all of those cells getting flagged
-    #[arg(long, default_value_t = false)]
+    #[arg(long, global = true, default_value_t = false)]
     assisted_flagging: bool,
 
-    /// If active, trying to open an open cell with N neighboring mines and N flagged adjacent cells will result in all
-    /// of those cells getting opened
-    #[arg(long, default_value_t = false)]
+    /// If active, trying to open an open cell chords on it, as pressing c does
+    #[arg(long, global = true, default_value_t = false)]
     assisted_opening: bool,
+
+    /// What chording on a number with c does. "classic" opens its other neighbours once all of its mines are flagged,
+    /// leaving the questioned ones closed. "all" opens the questioned ones too.
+    /// "assisted" does what "all" does, and flags the closed neighbours when they must all be mines
+    #[arg(long, global = true, default_value_t = ChordMode::Classic)]
+    chord: ChordMode,
+
+    /// If active, after every move each flag and open forced by a single number is done across the whole board,
+    /// until nothing else is forced. Games played this way are recorded apart in the stats
+    #[arg(long, global = true, default_value_t = false)]
+    auto_solve: bool,
+
+    /// "on" to place at most as many flags as mines, "off" to place as many as wanted.
+    /// The header shows how many mines are left, which is negative when there are more flags than mines
+    #[arg(long, global = true, default_value_t = FlagLimit::On)]
+    flag_limit: FlagLimit,
+
+    /// Generate every field from this seed, so that the same field can be played again.
+    /// The mines that would be around the first cell opened are moved to other cells,
+    /// and with --difficulty the field found also depends on that cell.
+    /// The seed of the current game is shown under the board
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+
+    /// The format boards get exported to from the end screen: "svg", "html" or "ansi" (text with colors, to be shown with `cat`)
+    #[arg(long, global = true, default_value_t = ExportFormat::Svg)]
+    export_format: ExportFormat,
+
+    /// Load the theme again whenever its file changes, without interrupting the game
+    #[arg(long, global = true)]
+    watch_theme: bool,
+
+    /// Let others watch the games live with `mnswpr watch`, connecting to this port or to the Unix socket at this path.
+    /// Paths must contain a `/`, e.g. "./mnswpr.sock"
+    #[arg(long, global = true)]
+    spectate: Option<String>,
+
+    /// Only play boards whose hardest needed deduction is in this band, e.g. "subset" or "trivial..global-count".
+    /// The difficulties, from the easiest, are "trivial", "subset", "global-count" and "guess".
+    /// Boards are generated again until one falls in the band, for up to 10 seconds
+    #[arg(long, global = true)]
+    difficulty: Option<DifficultyBand>,
+}
+
+#[derive(Subcommand, Clone)]
+enum Command {
+    /// Solve partially revealed boards using logic only: opening a cell that cannot be proven safe counts as a loss.
+    ///
+    /// Without options, a new board that can be cleared without guessing is generated for every game.
+    Puzzle {
+        /// Play the puzzle stored in this file. Each line is a row of the board, where `x` is a mine,
+        /// `-` is a closed empty cell, and `o` or a digit is an open cell
+        #[arg(long, conflicts_with = "pack")]
+        file: Option<String>,
+
+        /// Play the built-in puzzles that need this technique to be solved, one after the other.
+        /// One of "trivial", "subset", "global-count"
+        #[arg(long)]
+        pack: Option<Technique>,
+
+        /// Play every built-in puzzle, from the easiest technique to the hardest
+        #[arg(long, conflicts_with_all = ["file", "pack"])]
+        all: bool,
+    },
+    /// Let an external program play, talking to it through its stdin and stdout.
+    ///
+    /// The game runs headless, and the size of the field is not limited by the size of the terminal.
+    /// See the README for a description of the protocol.
+    Bot {
+        /// The command that starts the engine. It is run through `sh -c`
+        #[arg(long)]
+        engine: String,
+
+        /// The number of games to play
+        #[arg(short, long, default_value_t = 1)]
+        games: usize,
+    },
+    /// Let the built-in solver play many games and report how it went.
+    ///
+    /// The first click is always at the center of the field, and the size of the field is not limited
+    /// by the size of the terminal.
+    Bench {
+        /// The number of games to play
+        #[arg(short, long, default_value_t = 1000)]
+        games: usize,
+    },
+    /// Race against another player on the same board: wait for them to join, then send them the board.
+    ///
+    /// The size of the board and its seed are taken from the options of the host
+    Host {
+        /// The port the other player connects to
+        #[arg(long, default_value_t = net::DEFAULT_PORT)]
+        port: u16,
+    },
+    /// Race against the player hosting a game at this address, e.g. "192.168.1.20" or "localhost:7171"
+    Join {
+        /// The address of the host, followed by `:<port>` if the host did not use the default one
+        addr: String,
+    },
+    /// Clear a board together with other players, each with their own cursor
+    Coop {
+        #[command(subcommand)]
+        command: CoopCommand,
+    },
+    /// Play the board of the day, the same for everyone that day. Only the first attempt of the day is scored.
+    ///
+    /// The board is a small one with 20% of mines, generated from the date in UTC, with its center already open.
+    /// The result is added to the history of daily challenges and printed as a summary that can be shared
+    Daily {
+        /// Play the board of the day again once it was played, without recording the game
+        #[arg(long, conflicts_with = "history")]
+        practice: bool,
+
+        /// Show the result of every daily challenge played so far, instead of playing
+        #[arg(long)]
+        history: bool,
+    },
+    /// Watch the game of a player started with `--spectate`, without being able to play it
+    Watch {
+        /// The address of the game, e.g. "192.168.1.20:7272", or the path of a Unix socket, e.g. "./mnswpr.sock"
+        addr: String,
+    },
+    /// Work with custom themes
+    Theme {
+        #[command(subcommand)]
+        command: ThemeCommand,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum CoopCommand {
+    /// Start a game that other players can join at any time, until a mine is uncovered.
+    ///
+    /// The size of the board and its seed are taken from the options of the host
+    Host {
+        /// The port the other players connect to
+        #[arg(long, default_value_t = net::DEFAULT_PORT)]
+        port: u16,
+    },
+    /// Join the game hosted at this address, e.g. "192.168.1.20" or "localhost:7171"
+    Join {
+        /// The address of the host, followed by `:<port>` if the host did not use the default one
+        addr: String,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum ThemeCommand {
+    /// Report every problem found in a theme file, together with its line, and show a preview of the board
+    Check {
+        /// The theme file, or the name of a theme in $XDG_CONFIG_HOME/mnswpr/themes
+        file: String,
+    },
+    /// Show the built-in themes and the ones in $XDG_CONFIG_HOME/mnswpr/themes, each with a sample of its cells
+    List,
 }
 
-/// Returns (cols, rows) after parsing the cli arguments and clipping them with the size of the terminal minus some chars for padding
-fn parse_field_size(args: &Args) -> Result<(usize, usize)> {
+/// Returns the palette of the chosen theme, adapted to the options and to the colors supported by the terminal
+fn palette(args: &Args) -> Result<Palette> {
+    Ok(adapt_palette(args, args.theme.to_palette()?))
+}
+
+/// Adapts `palette` to the options and to the colors supported by the terminal
+fn adapt_palette(args: &Args, mut palette: Palette) -> Palette {
+    let mode = args.color_mode.unwrap_or_else(ColorMode::detect);
+    if let Some(set) = args.glyphs {
+        palette.glyphs = set.glyphs();
+    }
+    palette
+        .with_accessibility(args.accessibility)
+        .quantized(mode)
+}
+
+/// Returns the themes that can be switched between during a game, starting from the chosen one
+fn theme_cycle(args: &Args) -> ThemeCycle {
+    let options = args.clone();
+    ThemeCycle::new(&args.theme, args.watch_theme, move |palette| {
+        adapt_palette(&options, palette)
+    })
+}
+
+/// Returns (cols, rows) after parsing the cli arguments, without taking the size of the terminal into account
+fn requested_field_size(args: &Args) -> (u64, u64) {
+    (
+        args.cols.unwrap_or(args.preset.to_size().0),
+        args.rows.unwrap_or(args.preset.to_size().1),
+    )
+}
+
+/// Returns (cols, rows) after parsing the cli arguments and clipping them with the size of the terminal minus some chars for padding.
+/// `cell_width` is the number of terminal columns taken by each cell
+fn parse_field_size(args: &Args, cell_width: usize) -> Result<(usize, usize)> {
     let termsize = termion::terminal_size()?;
 
-    let cols = if args.cols.is_some() {
-        args.cols.unwrap()
-    } else {
-        args.preset.to_size().0
-    };
-    // -2 is to have a little bit of padding, division by 3 is because we need to have enough space to print 3 chars for each tile
-    let cols = (cols).min((termsize.0 as u64 - 2) / 3) as usize;
+    let (cols, rows) = requested_field_size(args);
+    // -2 is to have a little bit of padding, division by the cell width is because we need to have enough space to print each tile
+    let cols = (cols).min((termsize.0 as u64).saturating_sub(2) / cell_width as u64) as usize;
 
-    let rows = if args.rows.is_some() {
-        args.rows.unwrap()
-    } else {
-        args.preset.to_size().1
-    };
     let rows = rows.min(termsize.1 as u64 - 4) as usize;
 
     Ok((cols, rows))
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+/// What the user chose on the end screen
+enum EndScreenChoice {
+    PlayAgain,
+    Quit,
+    Analyze,
+    Export,
+}
 
-    let (cols, rows) = parse_field_size(&args).context("Could not get the size of the terminal")?;
+/// Prints the end of game screen and waits for the user to choose whether to play again.
+/// After a loss, the user can also look at the analysis of the game. The board can be exported to a file.
+/// Returns true if the user wants to play again
+fn show_end_screen(
+    stdout: &mut HideCursor<RawTerminal<Stdout>>,
+    input: &Input,
+    mnswpr: &mut Mnswpr,
+    outcome: &Outcome,
+    export_format: ExportFormat,
+) -> Result<bool> {
+    let can_analyze = matches!(outcome, Outcome::Lost);
+    let mut exported = None;
 
-    let mut mnswpr = Mnswpr::new(rows, cols, args.mine_percentage, args.theme.to_palette()?);
+    loop {
+        write!(stdout, "{}", termion::clear::All)?;
+        mnswpr.invalidate_frame();
+        mnswpr.print_game_state(stdout, true)?;
+        let result = match outcome {
+            Outcome::Won => "You won!",
+            Outcome::Lost => "You lost!",
+            Outcome::Guessed => "You guessed! That cell could not be proven safe.",
+            Outcome::Quit | Outcome::NewGame | Outcome::Restart | Outcome::RaceOver => {
+                return Ok(false)
+            }
+        };
+        let result_color = match outcome {
+            Outcome::Won => color::Fg(color::Green).to_string(),
+            _ => color::Fg(color::LightRed).to_string(),
+        };
+        write!(stdout, "{result_color}{result}{FG_RESET}\r\n")?;
+        if let Some(rating) = &mnswpr.replay.rating {
+            write!(stdout, "Difficulty: {rating}\r\n")?;
+        }
+        if can_analyze {
+            write!(stdout, "Press a/A to see the analysis of your moves\r\n")?;
+        }
+        match &exported {
+            Some(path) => write!(stdout, "Board exported to {path}\r\n")?,
+            None => write!(
+                stdout,
+                "Press e/E to export the board to a file ({export_format})\r\n"
+            )?,
+        }
+        write!(
+            stdout,
+            "Press y/Y/<space>/<insert> if you want to play again, otherwise press n/N\r\n"
+        )?;
+        stdout.flush()?;
 
-    let mut stdout = HideCursor::from(stdout().into_raw_mode()?);
+        match wait_for_end_screen_key(input, can_analyze)? {
+            EndScreenChoice::PlayAgain => return Ok(true),
+            EndScreenChoice::Quit => return Ok(false),
+            EndScreenChoice::Analyze => show_analysis(stdout, input, mnswpr)?,
+            EndScreenChoice::Export => {
+                let title = format!(
+                    "{result} Mines: {}    Flags: {}",
+                    mnswpr.mine_count, mnswpr.flag_count
+                );
+                let path = export::save(export_format, &mnswpr.field, mnswpr.palette(), &title)?;
+                exported = Some(path.display().to_string());
+            }
+        }
+    }
+}
+
+/// Waits for the user to choose on the end screen
+fn wait_for_end_screen_key(input: &Input, can_analyze: bool) -> Result<EndScreenChoice> {
+    for key in input.keys() {
+        match key? {
+            Key::Char(' ' | 'y' | 'Y' | '\n') => return Ok(EndScreenChoice::PlayAgain),
+            Key::Char('q' | 'Q' | 'n' | 'N') => return Ok(EndScreenChoice::Quit),
+            Key::Char('a' | 'A') if can_analyze => return Ok(EndScreenChoice::Analyze),
+            Key::Char('e' | 'E') => return Ok(EndScreenChoice::Export),
+            _ => {}
+        }
+    }
+    Ok(EndScreenChoice::Quit)
+}
+
+/// Shows the analysis of the last game until a key is pressed
+fn show_analysis(
+    stdout: &mut HideCursor<RawTerminal<Stdout>>,
+    input: &Input,
+    mnswpr: &mut Mnswpr,
+) -> Result<()> {
+    write!(stdout, "{}", termion::clear::All)?;
+    mnswpr.invalidate_frame();
+    mnswpr.print_game_state(stdout, true)?;
+
+    let available_lines = (termion::terminal_size()?.1 as usize).saturating_sub(mnswpr.rows + 2);
+    let analysis = postmortem::analyze(&mnswpr.replay);
+    postmortem::print_analysis(stdout, &analysis, available_lines.saturating_sub(1))?;
+    write!(stdout, "Press any key to go back\r\n")?;
+    stdout.flush()?;
+
+    if let Some(key) = input.keys().next() {
+        key?;
+    }
+    Ok(())
+}
+
+/// Switches the terminal to raw mode, and starts reading the keys
+fn open_terminal() -> Result<(HideCursor<RawTerminal<Stdout>>, Input)> {
+    let stdout = HideCursor::from(stdout().into_raw_mode()?);
+    Ok((stdout, Input::spawn()))
+}
+
+/// Plays classic games until the player quits. If `resume` is given, the first game continues from it.
+/// A game quit before it ended gets saved, so that it can be resumed from the menu
+fn play_classic(
+    args: &Args,
+    stdout: &mut HideCursor<RawTerminal<Stdout>>,
+    input: &Input,
+    resume: Option<Save>,
+) -> Result<()> {
+    let palette = palette(args)?;
+    // A game saved in a larger terminal is kept as it is, to be resumed once the terminal is large enough
+    if let Some(save) = &resume {
+        ensure_board_fits(save.replay.rows, save.replay.cols, &palette)?;
+    }
+    let (cols, rows) = parse_field_size(args, palette.glyphs.cell_width())
+        .context("Could not get the size of the terminal")?;
+    let mine_percentage = resume
+        .as_ref()
+        .map_or(args.mine_percentage, |save| save.mine_percentage);
+
+    let mut mnswpr = Mnswpr::new(rows, cols, mine_percentage, palette);
+    mnswpr.difficulty = args.difficulty;
+    mnswpr.assisted_opening = args.assisted_opening;
+    mnswpr.chord_mode = args.chord;
+    mnswpr.auto_solve = args.auto_solve;
+    mnswpr.assisted_flagging = args.assisted_flagging;
+    mnswpr.unlimited_flags = args.flag_limit == FlagLimit::Off;
+    mnswpr.seed = args.seed;
+    if args.cols.is_none() && args.rows.is_none() && resume.is_none() {
+        mnswpr.preset = Some(args.preset.clone());
+    }
+    mnswpr.themes = Some(theme_cycle(args));
+    mnswpr.spectators = spectators(args)?;
 
+    let mut resume = resume.map(|save| (save.replay, Duration::from_secs_f64(save.seconds)));
+    let mut restart = false;
     loop {
         write!(
             stdout,
@@ -108,46 +493,617 @@ fn main() -> Result<()> {
             termion::clear::All,
             termion::cursor::Goto(1, 1)
         )?;
-        mnswpr.reset();
+        match resume.take() {
+            Some((replay, elapsed)) => mnswpr.resume(replay, elapsed),
+            None if restart => mnswpr.restart(),
+            None => mnswpr.reset(),
+        }
+        restart = false;
 
-        let user_did_win = mnswpr.play(&mut stdout, args.assisted_opening, args.assisted_flagging)?;
+        let outcome = mnswpr.play(stdout, input)?;
+        if matches!(outcome, Outcome::Quit) && !mnswpr.replay.events.is_empty() {
+            let seconds = mnswpr.timer.elapsed().as_secs_f64();
+            Save::new(mnswpr.replay.clone(), mine_percentage, seconds).store()?;
+        }
+        record_game(&mnswpr, &outcome)?;
 
-        // If the user explicitly quit, then exit out of the program
-        if user_did_win.is_none() {
+        match outcome {
+            Outcome::NewGame => continue,
+            Outcome::Restart => {
+                restart = true;
+                continue;
+            }
+            _ => {}
+        }
+        if !show_end_screen(stdout, input, &mut mnswpr, &outcome, args.export_format)? {
             break;
         }
+    }
 
-        mnswpr.print_game_state(&mut stdout, true)?;
-        if user_did_win.unwrap() {
-            write!(stdout, "{}You won!{FG_RESET}\r\n", color::Fg(color::Green))?;
-        } else {
-            write!(
-                stdout,
-                "{}You lost!{FG_RESET}\r\n",
-                color::Fg(color::LightRed),
-            )?;
+    Ok(())
+}
+
+/// Saves the result of a finished or abandoned game in the stats.
+/// Games that were quit, and boards left before any move, are not saved
+fn record_game(mnswpr: &Mnswpr, outcome: &Outcome) -> Result<()> {
+    let result = match outcome {
+        Outcome::Won => GameResult::Won,
+        Outcome::Lost | Outcome::Guessed => GameResult::Lost,
+        Outcome::NewGame | Outcome::Restart if !mnswpr.replay.events.is_empty() => {
+            GameResult::Abandoned
+        }
+        Outcome::NewGame | Outcome::Restart | Outcome::Quit | Outcome::RaceOver => return Ok(()),
+    };
+
+    let mut record = GameRecord::new(mnswpr.rows, mnswpr.cols, mnswpr.mine_count, result);
+    record.rating = mnswpr.replay.rating;
+    record.assist = mnswpr.assist_level();
+    record.seconds = Some(mnswpr.timer.elapsed().as_secs_f64());
+    (record.pauses, record.paused_seconds) = mnswpr.replay.pauses();
+    Stats::record(record)
+}
+
+/// Generates the board of a game played over the network from the seed given in the options, or from a random one.
+/// Every player starts with the same cells open: the area around the center of the board
+fn shared_board(args: &Args, rows: usize, cols: usize) -> Replay {
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let mut replay = Replay::seeded(
+        &mut StdRng::seed_from_u64(seed),
+        rows,
+        cols,
+        args.mine_percentage,
+        args.flag_limit == FlagLimit::Off,
+    );
+    replay.seed = Some(seed);
+    replay
+}
+
+/// Plays the daily challenge of `date`, then shows how it went until a key is pressed.
+/// Unless `practice`, the attempt is recorded and its shareable summary returned.
+/// The attempt starts as soon as the board is shown, and counts as abandoned until the game ends
+fn play_daily(
+    args: &Args,
+    date: Date,
+    practice: bool,
+    stdout: &mut HideCursor<RawTerminal<Stdout>>,
+    input: &Input,
+) -> Result<Option<String>> {
+    let palette = palette(args)?;
+    let replay = daily::board(date);
+    ensure_board_fits(replay.rows, replay.cols, &palette)?;
+
+    let mut mnswpr = Mnswpr::from_field(replay.initial_field(), palette);
+    mnswpr.assisted_opening = args.assisted_opening;
+    mnswpr.chord_mode = args.chord;
+    mnswpr.auto_solve = args.auto_solve;
+    mnswpr.assisted_flagging = args.assisted_flagging;
+    mnswpr.preset = Some(daily::PRESET);
+    mnswpr.themes = Some(theme_cycle(args));
+    mnswpr.spectators = spectators(args)?;
+    mnswpr.daily = Some(if practice {
+        format!("{date}, practice")
+    } else {
+        date.to_string()
+    });
+    mnswpr.resume(replay, Duration::ZERO);
+
+    let empty = mnswpr.rows * mnswpr.cols - mnswpr.mine_count;
+    let attempt = |mnswpr: &Mnswpr, result| DailyRecord {
+        date: date.to_string(),
+        result,
+        open: empty - mnswpr.closed_empty_cells,
+        empty,
+        seconds: mnswpr.timer.elapsed().as_secs_f64(),
+        assist: mnswpr.assist_level(),
+    };
+    // The center of the board is open, so it can be studied before the first move:
+    // the attempt and the clock start right away, and an attempt cut short stays abandoned
+    if !practice {
+        Stats::record_daily(attempt(&mnswpr, GameResult::Abandoned))?;
+    }
+    mnswpr.timer.start();
+
+    write!(
+        stdout,
+        "{}{}",
+        termion::clear::All,
+        termion::cursor::Goto(1, 1)
+    )?;
+    let outcome = mnswpr.play(stdout, input)?;
+    let result = match outcome {
+        Outcome::Won => GameResult::Won,
+        Outcome::Lost | Outcome::Guessed => GameResult::Lost,
+        _ => GameResult::Abandoned,
+    };
+
+    let record = attempt(&mnswpr, result);
+    let summary = daily::summary(&record);
+    if !practice {
+        record_game(&mnswpr, &outcome)?;
+        Stats::update_daily(record)?;
+    }
+    if result == GameResult::Abandoned {
+        return Ok((!practice).then_some(summary));
+    }
+
+    write!(stdout, "{}", termion::clear::All)?;
+    mnswpr.invalidate_frame();
+    mnswpr.print_game_state(stdout, true)?;
+    let (message, result_color) = match result {
+        GameResult::Won => (
+            "You cleared the board of the day!",
+            color::Fg(color::Green).to_string(),
+        ),
+        _ => ("You lost!", color::Fg(color::LightRed).to_string()),
+    };
+    write!(
+        stdout,
+        "{result_color}{message}{FG_RESET}\r\n{}\r\n",
+        summary.replace('\n', "\r\n")
+    )?;
+    if practice {
+        write!(stdout, "This was a practice game, it was not recorded\r\n")?;
+    }
+    write!(stdout, "Press any key to quit\r\n")?;
+    stdout.flush()?;
+
+    if let Some(key) = input.keys().next() {
+        key?;
+    }
+    Ok((!practice).then_some(summary))
+}
+
+/// Prints the result of every daily challenge that was played, from the oldest
+fn print_daily_history() -> Result<()> {
+    let stats = Stats::load()?;
+    if stats.daily.is_empty() {
+        println!("No daily challenge was played yet, start today's with `mnswpr daily`");
+    }
+    for record in &stats.daily {
+        let result = match record.result {
+            GameResult::Won => "cleared",
+            GameResult::Lost => "lost",
+            GameResult::Abandoned => "gave up",
+        };
+        println!(
+            "{}: {result} in {:.0}s, {}/{} cells open",
+            record.date, record.seconds, record.open, record.empty
+        );
+    }
+    Ok(())
+}
+
+/// Starts publishing the games to spectators, if `--spectate` was given
+fn spectators(args: &Args) -> Result<Option<Spectators>> {
+    args.spectate.as_deref().map(Spectators::listen).transpose()
+}
+
+/// Shows the game published at the other end of `watch`, which starts from `start`, until it is no longer published
+fn watch_game(
+    args: &Args,
+    watch: &Watch,
+    start: Message,
+    stdout: &mut HideCursor<RawTerminal<Stdout>>,
+    input: &Input,
+) -> Result<()> {
+    let Message::Board { view, .. } = &start else {
+        bail!("The game did not start with its board");
+    };
+    let palette = palette(args)?;
+    ensure_board_fits(view.rows, view.cols, &palette)?;
+
+    let mut mnswpr = Mnswpr::new(view.rows, view.cols, 0, palette);
+    write!(
+        stdout,
+        "{}{}",
+        termion::clear::All,
+        termion::cursor::Goto(1, 1)
+    )?;
+    mnswpr.watch(watch, start, stdout, input)
+}
+
+/// Fails if a board of `rows` by `cols`, which does not come from the size options, is too big for this terminal
+fn ensure_board_fits(rows: usize, cols: usize, palette: &Palette) -> Result<()> {
+    let termsize = termion::terminal_size()?;
+    if cols * palette.glyphs.cell_width() > (termsize.0 as usize).saturating_sub(2)
+        || rows > (termsize.1 as usize).saturating_sub(4)
+    {
+        bail!("The {cols}x{rows} board does not fit in this terminal");
+    }
+    Ok(())
+}
+
+/// Plays the race on the board of `replay`, then shows who won until a key is pressed
+fn play_versus(
+    args: &Args,
+    versus: Versus,
+    replay: Replay,
+    stdout: &mut HideCursor<RawTerminal<Stdout>>,
+    input: &Input,
+) -> Result<()> {
+    let palette = palette(args)?;
+    ensure_board_fits(replay.rows, replay.cols, &palette)?;
+
+    // Auto-solve is left out, since it would play most of the race for one of the players
+    let mut mnswpr = Mnswpr::from_field(replay.initial_field(), palette);
+    mnswpr.assisted_opening = args.assisted_opening;
+    mnswpr.assisted_flagging = args.assisted_flagging;
+    mnswpr.chord_mode = args.chord;
+    mnswpr.unlimited_flags = replay.unlimited_flags;
+    mnswpr.themes = Some(theme_cycle(args));
+    mnswpr.versus = Some(versus);
+    mnswpr.spectators = spectators(args)?;
+    mnswpr.reset();
+
+    write!(
+        stdout,
+        "{}{}",
+        termion::clear::All,
+        termion::cursor::Goto(1, 1)
+    )?;
+    let result = match mnswpr.play(stdout, input)? {
+        Outcome::Quit => return Ok(()),
+        _ => match mnswpr.wait_for_race(stdout, input)? {
+            Some(result) => result,
+            None => return Ok(()),
+        },
+    };
+
+    write!(stdout, "{}", termion::clear::All)?;
+    mnswpr.invalidate_frame();
+    mnswpr.print_game_state(stdout, true)?;
+    let (message, result_color) = match result {
+        RaceResult::Won => ("You won the race!", color::Fg(color::Green).to_string()),
+        RaceResult::Lost => ("You lost the race!", color::Fg(color::LightRed).to_string()),
+        RaceResult::Draw => ("It's a draw!", color::Fg(color::Yellow).to_string()),
+        RaceResult::OpponentLeft => (
+            "The other player left the race",
+            color::Fg(color::Yellow).to_string(),
+        ),
+    };
+    write!(
+        stdout,
+        "{result_color}{message}{FG_RESET}\r\nPress any key to quit\r\n"
+    )?;
+    stdout.flush()?;
+
+    if let Some(key) = input.keys().next() {
+        key?;
+    }
+    Ok(())
+}
+
+/// Plays the co-op game on the board of `replay`, which holds every move made before this player joined,
+/// then shows how it ended until a key is pressed
+fn play_coop(
+    args: &Args,
+    coop: Coop,
+    replay: Replay,
+    stdout: &mut HideCursor<RawTerminal<Stdout>>,
+    input: &Input,
+) -> Result<()> {
+    let palette = palette(args)?;
+    ensure_board_fits(replay.rows, replay.cols, &palette)?;
+
+    // Auto-solve is left out, since the moves it makes would race the ones of the other players
+    let mut mnswpr = Mnswpr::from_field(replay.initial_field(), palette);
+    mnswpr.assisted_opening = args.assisted_opening;
+    mnswpr.assisted_flagging = args.assisted_flagging;
+    mnswpr.chord_mode = args.chord;
+    mnswpr.unlimited_flags = replay.unlimited_flags;
+    mnswpr.themes = Some(theme_cycle(args));
+    mnswpr.coop = Some(coop);
+    mnswpr.spectators = spectators(args)?;
+    mnswpr.resume(replay, Duration::ZERO);
+
+    write!(
+        stdout,
+        "{}{}",
+        termion::clear::All,
+        termion::cursor::Goto(1, 1)
+    )?;
+    let outcome = mnswpr.play(stdout, input)?;
+    let Some(coop) = &mnswpr.coop else {
+        return Ok(());
+    };
+    let (message, result_color) = match outcome {
+        Outcome::Won => (
+            String::from("Your team cleared the board!"),
+            color::Fg(color::Green).to_string(),
+        ),
+        Outcome::Lost => {
+            let player = coop.mine_uncovered_by().unwrap_or(coop.player());
+            let message = if player == coop.player() {
+                String::from("You uncovered a mine, the game is over for everyone")
+            } else {
+                format!(
+                    "Player {} uncovered a mine, the game is over for everyone",
+                    player + 1
+                )
+            };
+            (message, color::Fg(color::LightRed).to_string())
+        }
+        _ if coop.host_left() => (
+            String::from("The host left the game"),
+            color::Fg(color::Yellow).to_string(),
+        ),
+        _ => return Ok(()),
+    };
+
+    write!(stdout, "{}", termion::clear::All)?;
+    mnswpr.invalidate_frame();
+    mnswpr.print_game_state(stdout, true)?;
+    write!(
+        stdout,
+        "{result_color}{message}{FG_RESET}\r\nPress any key to quit\r\n"
+    )?;
+    stdout.flush()?;
+
+    if let Some(key) = input.keys().next() {
+        key?;
+    }
+    Ok(())
+}
+
+/// Plays the puzzles one after the other. Moves on to the next puzzle only after the current one is solved,
+/// or when the player asks for a new one, which puts the current one at the end of the list.
+/// If `puzzles` is empty, a new puzzle gets generated for every game
+fn play_puzzles(
+    args: &Args,
+    mut puzzles: Vec<Puzzle>,
+    stdout: &mut HideCursor<RawTerminal<Stdout>>,
+    input: &Input,
+) -> Result<()> {
+    let generate = puzzles.is_empty();
+    let mut palette = palette(args)?;
+    let (cols, rows) = parse_field_size(args, palette.glyphs.cell_width())
+        .context("Could not get the size of the terminal")?;
+    let mut themes = theme_cycle(args);
+    let mut spectators = spectators(args)?;
+
+    loop {
+        if generate && puzzles.is_empty() {
+            puzzles.push(Puzzle::generate(rows, cols, args.mine_percentage)?);
         }
+        let Some(puzzle) = puzzles.first() else {
+            write!(stdout, "You solved every puzzle!\r\n")?;
+            break;
+        };
+        // The puzzles of a file have their own size, and the glyphs of the theme may have become wider
+        ensure_board_fits(puzzle.field.rows, puzzle.field.cols, &palette)?;
+
+        // The theme may be switched during a puzzle, and is kept for the next one
+        let mut mnswpr = Mnswpr::from_field(puzzle.field.clone(), palette);
+        mnswpr.no_guess = true;
+        mnswpr.assisted_opening = args.assisted_opening;
+        mnswpr.chord_mode = args.chord;
+        mnswpr.auto_solve = args.auto_solve;
+        mnswpr.assisted_flagging = args.assisted_flagging;
+        mnswpr.unlimited_flags = args.flag_limit == FlagLimit::Off;
+        mnswpr.themes = Some(themes);
+        mnswpr.spectators = spectators;
+
         write!(
             stdout,
-            "Press y/Y/<space>/<insert> if you want to play again, otherwise press n/N\r\n"
+            "{}{}",
+            termion::clear::All,
+            termion::cursor::Goto(1, 1)
         )?;
-        stdout.flush()?;
+        mnswpr.reset();
 
-        let stdin = stdin();
-        for e in stdin.events() {
-            if let Event::Key(event) = e? {
-                match event {
-                    Key::Char(' ' | 'y' | 'Y' | '\n') => {
-                        break;
-                    }
-                    Key::Char('q' | 'Q' | 'n' | 'N') => {
-                        return Ok(());
-                    }
-                    _ => {}
-                }
+        let outcome = mnswpr.play(stdout, input)?;
+        match outcome {
+            Outcome::Restart => {}
+            Outcome::NewGame if !generate => puzzles.rotate_left(1),
+            Outcome::Won | Outcome::NewGame => {
+                puzzles.remove(0);
             }
+            _ if generate => {
+                puzzles.remove(0);
+            }
+            _ => {}
+        }
+
+        let abandoned = matches!(outcome, Outcome::NewGame | Outcome::Restart);
+        if !abandoned && !show_end_screen(stdout, input, &mut mnswpr, &outcome, args.export_format)?
+        {
+            break;
         }
+        palette = mnswpr.palette().clone();
+        themes = mnswpr
+            .themes
+            .take()
+            .expect("themes were set before the game");
+        spectators = mnswpr.spectators.take();
+    }
+
+    Ok(())
+}
+
+/// Plays `games` games with the engine started by `cmd`, printing the result of each one
+fn play_bot(args: &Args, cmd: &str, games: usize) -> Result<()> {
+    let (cols, rows) = requested_field_size(args);
+    let mut field = Field::new(rows as usize, cols as usize);
+    field.unlimited_flags = args.flag_limit == FlagLimit::Off;
+    let mut engine = Engine::spawn(cmd)?;
+
+    let mut won = 0;
+    for game in 1..=games {
+        let outcome = engine.play(&mut field, args.mine_percentage)?;
+        let result = match outcome {
+            Outcome::Won => {
+                won += 1;
+                "won"
+            }
+            Outcome::Lost | Outcome::Guessed => "lost",
+            Outcome::Quit | Outcome::NewGame | Outcome::Restart | Outcome::RaceOver => "quit",
+        };
+        println!("Game {game}: {result}");
+    }
+    engine.finish()?;
+
+    println!("Won {won}/{games} games");
+    Ok(())
+}
+
+/// Prints the problems of the theme at `path`, followed by a preview of the board if the theme can be loaded
+fn check_theme(args: &Args, file: &str) -> Result<()> {
+    let path = match theme::find(file) {
+        Some(path) if !Path::new(file).exists() => path,
+        _ => PathBuf::from(file),
+    };
+    let path = path.as_path();
+    let source = fs::read_to_string(path).context("Could not read theme data.")?;
+    let problems = theme::check(&source, path.parent().unwrap_or(Path::new(".")));
+    for problem in &problems {
+        println!("{}: {problem}", path.display());
+    }
+
+    let errors = problems
+        .iter()
+        .filter(|p| p.severity == theme::Severity::Error)
+        .count();
+    if errors > 0 {
+        bail!("The theme has {errors} error(s)");
+    }
+    if problems.is_empty() {
+        println!("{}: no problems found", path.display());
     }
 
+    let palette = adapt_palette(args, theme::load(path)?);
+    print!("\n{}", theme::preview(&palette));
     Ok(())
 }
+
+/// Prints the built-in themes and the user themes, each with a sample of its cells
+fn list_themes(args: &Args) -> Result<()> {
+    let user_themes = theme::user_themes();
+    let width = user_themes
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain([Theme::Mnswpr.to_string().len()])
+        .max()
+        .unwrap_or(0);
+
+    println!("Built-in themes:");
+    for theme in [Theme::Mnswpr, Theme::OG] {
+        let sample = theme::sample(&adapt_palette(args, theme.to_palette()?));
+        println!("  {:width$}  {sample}", theme.to_string());
+    }
+
+    let dir = theme::themes_dir().map_or(String::from("$XDG_CONFIG_HOME/mnswpr/themes"), |dir| {
+        dir.display().to_string()
+    });
+    if user_themes.is_empty() {
+        println!("No themes in {dir}");
+        return Ok(());
+    }
+    println!("Themes in {dir}:");
+    for (name, path) in user_themes {
+        match theme::load(&path) {
+            Ok(palette) => {
+                println!(
+                    "  {name:width$}  {}",
+                    theme::sample(&adapt_palette(args, palette))
+                )
+            }
+            Err(_) => println!("  {name:width$}  invalid, see `mnswpr theme check {name}`"),
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    // Without any argument, the game starts from the menu
+    let show_menu = std::env::args_os().len() == 1;
+    let mut args = Args::parse();
+
+    match &args.command {
+        None if show_menu => {
+            let (mut stdout, input) = open_terminal()?;
+            menu::run(&mut stdout, &input, &mut args)
+        }
+        None => {
+            let (mut stdout, input) = open_terminal()?;
+            play_classic(&args, &mut stdout, &input, None)
+        }
+        Some(Command::Puzzle { file, pack, all }) => {
+            let puzzles = match file {
+                Some(path) => vec![Puzzle::from_file(path)?],
+                None if *all || pack.is_some() => puzzle::builtin_pack(*pack),
+                None => Vec::new(),
+            };
+            let (mut stdout, input) = open_terminal()?;
+            play_puzzles(&args, puzzles, &mut stdout, &input)
+        }
+        Some(Command::Bot { engine, games }) => play_bot(&args, engine, *games),
+        Some(Command::Bench { games }) => {
+            let (cols, rows) = requested_field_size(&args);
+            print!(
+                "{}",
+                bench::run(rows as usize, cols as usize, args.mine_percentage, *games)
+            );
+            Ok(())
+        }
+        Some(Command::Host { port }) => {
+            let (cols, rows) = parse_field_size(&args, palette(&args)?.glyphs.cell_width())?;
+            let replay = shared_board(&args, rows, cols);
+            let versus = Versus::host(*port, &replay)?;
+            let (mut stdout, input) = open_terminal()?;
+            play_versus(&args, versus, replay, &mut stdout, &input)
+        }
+        Some(Command::Join { addr }) => {
+            let (versus, replay) = Versus::join(addr)?;
+            let (mut stdout, input) = open_terminal()?;
+            play_versus(&args, versus, replay, &mut stdout, &input)
+        }
+        Some(Command::Coop { command }) => {
+            let (coop, replay) = match command {
+                CoopCommand::Host { port } => {
+                    let (cols, rows) =
+                        parse_field_size(&args, palette(&args)?.glyphs.cell_width())?;
+                    (Coop::host(*port)?, shared_board(&args, rows, cols))
+                }
+                CoopCommand::Join { addr } => Coop::join(addr)?,
+            };
+            let (mut stdout, input) = open_terminal()?;
+            play_coop(&args, coop, replay, &mut stdout, &input)
+        }
+        Some(Command::Daily { practice, history }) => {
+            if *history {
+                return print_daily_history();
+            }
+
+            let date = Date::today();
+            let stats = Stats::load()?;
+            match (stats.daily_record(&date.to_string()), practice) {
+                (Some(record), false) => {
+                    println!("{}\n", daily::summary(record));
+                    println!("The board of today was already played, play it again without recording it with `mnswpr daily --practice`");
+                    return Ok(());
+                }
+                (None, true) => bail!("The board of today was not played yet: its first attempt is the one that counts"),
+                _ => {}
+            }
+
+            // The terminal is back to normal once the game is over, so that the summary can be copied
+            let summary = {
+                let (mut stdout, input) = open_terminal()?;
+                play_daily(&args, date, *practice, &mut stdout, &input)?
+            };
+            if let Some(summary) = summary {
+                println!("{summary}");
+            }
+            Ok(())
+        }
+        Some(Command::Watch { addr }) => {
+            let (watch, start) = Watch::connect(addr)?;
+            let (mut stdout, input) = open_terminal()?;
+            watch_game(&args, &watch, start, &mut stdout, &input)
+        }
+        Some(Command::Theme { command }) => match command {
+            ThemeCommand::Check { file } => check_theme(&args, file),
+            ThemeCommand::List => list_themes(&args),
+        },
+    }
+}
@@ -0,0 +1,526 @@
+//! The start menu, shown when mnswpr is run without arguments.
+//! Every setting in it is one of the command line options, so the menu edits `Args` directly
+
+use std::collections::BTreeMap;
+use std::io::{Stdout, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use termion::cursor::{Goto, HideCursor};
+use termion::event::Key;
+use termion::raw::RawTerminal;
+use termion::style;
+
+use crate::config::{ChordMode, FlagLimit, SizePreset, Theme};
+use crate::glyphs::GlyphSet;
+use crate::input::Input;
+use crate::save::{self, Save};
+use crate::stats::{AssistLevel, GameRecord, GameResult, Stats};
+use crate::{play_classic, play_puzzles, theme, Args};
+
+const PRESETS: [SizePreset; 5] = [
+    SizePreset::Tiny,
+    SizePreset::Small,
+    SizePreset::Medium,
+    SizePreset::Large,
+    SizePreset::Huge,
+];
+
+const GLYPH_SETS: [Option<GlyphSet>; 4] = [
+    None,
+    Some(GlyphSet::Ascii),
+    Some(GlyphSet::Unicode),
+    Some(GlyphSet::NerdFont),
+];
+
+const CHORD_MODES: [ChordMode; 3] = [ChordMode::Classic, ChordMode::Assisted, ChordMode::All];
+
+/// The largest value that can be typed in the number of columns or rows
+const MAX_SIZE: u64 = 999;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Mode {
+    Classic,
+    /// Generated puzzles, as with `mnswpr puzzle`
+    Puzzle,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Item {
+    Start,
+    Resume,
+    Mode,
+    Preset,
+    Columns,
+    Rows,
+    MinePercentage,
+    Theme,
+    Glyphs,
+    AssistedOpening,
+    AssistedFlagging,
+    Chord,
+    AutoSolve,
+    FlagLimit,
+    Stats,
+    Quit,
+}
+
+struct Menu<'a> {
+    args: &'a mut Args,
+    mode: Mode,
+    /// The themes that can be picked: the built-in ones, the user themes and the one given on the command line
+    themes: Vec<Theme>,
+    /// The index of the selected item in `items()`
+    selected: usize,
+    /// Shown under the menu until the next key is pressed
+    message: Option<String>,
+}
+
+impl Menu<'_> {
+    /// Returns the items of the menu. The number of columns and rows can only be changed with the custom size,
+    /// and saved games can only be resumed if there are some
+    fn items(&self, has_saves: bool) -> Vec<Item> {
+        let mut items = vec![Item::Start];
+        if has_saves {
+            items.push(Item::Resume);
+        }
+        items.extend([Item::Mode, Item::Preset]);
+        if self.custom_size() {
+            items.extend([Item::Columns, Item::Rows]);
+        }
+        items.extend([
+            Item::MinePercentage,
+            Item::Theme,
+            Item::Glyphs,
+            Item::AssistedOpening,
+            Item::AssistedFlagging,
+            Item::Chord,
+            Item::AutoSolve,
+            Item::FlagLimit,
+            Item::Stats,
+            Item::Quit,
+        ]);
+        items
+    }
+
+    /// Whether the size of the field is given by the number of columns and rows instead of the preset
+    fn custom_size(&self) -> bool {
+        self.args.cols.is_some() || self.args.rows.is_some()
+    }
+
+    /// Returns the text of `item`: its name, and its value if it is a setting
+    fn label(&self, item: Item) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        let (name, value) = match item {
+            Item::Start => return String::from("Start"),
+            Item::Resume => return String::from("Resume a saved game"),
+            Item::Stats => return String::from("Stats"),
+            Item::Quit => return String::from("Quit"),
+            Item::Mode => (
+                "Mode",
+                match self.mode {
+                    Mode::Classic => String::from("classic"),
+                    Mode::Puzzle => String::from("puzzle"),
+                },
+            ),
+            Item::Preset if self.custom_size() => ("Size", String::from("custom")),
+            Item::Preset => ("Size", self.args.preset.to_string()),
+            Item::Columns => ("Columns", self.args.cols.unwrap_or_default().to_string()),
+            Item::Rows => ("Rows", self.args.rows.unwrap_or_default().to_string()),
+            Item::MinePercentage => ("Mines", format!("{}%", self.args.mine_percentage)),
+            Item::Theme => ("Theme", self.args.theme.name()),
+            Item::Glyphs => (
+                "Glyphs",
+                self.args
+                    .glyphs
+                    .map_or(String::from("from the theme"), |set| set.to_string()),
+            ),
+            Item::AssistedOpening => ("Assisted opening", on_off(self.args.assisted_opening)),
+            Item::AssistedFlagging => ("Assisted flagging", on_off(self.args.assisted_flagging)),
+            Item::Chord => ("Chord", self.args.chord.to_string()),
+            Item::AutoSolve => ("Auto-solve trivial", on_off(self.args.auto_solve)),
+            Item::FlagLimit => ("Flag limit", self.args.flag_limit.to_string()),
+        };
+        format!("{name:18}< {value} >")
+    }
+
+    /// Switches the setting `item` to its next value, or to the previous one if `forward == false`
+    fn change(&mut self, item: Item, forward: bool) {
+        let step = |idx: usize, len: usize| {
+            if forward {
+                (idx + 1) % len
+            } else {
+                (idx + len - 1) % len
+            }
+        };
+        let add = |value: u64, min: u64, max: u64| {
+            if forward {
+                (value + 1).min(max)
+            } else {
+                value.saturating_sub(1).max(min)
+            }
+        };
+
+        match item {
+            Item::Mode => {
+                self.mode = match self.mode {
+                    Mode::Classic => Mode::Puzzle,
+                    Mode::Puzzle => Mode::Classic,
+                }
+            }
+            Item::Preset => {
+                // The custom size comes after the presets, and starts from the size of the last preset
+                let idx = if self.custom_size() {
+                    PRESETS.len()
+                } else {
+                    PRESETS
+                        .iter()
+                        .position(|p| *p == self.args.preset)
+                        .unwrap_or(0)
+                };
+                match PRESETS.get(step(idx, PRESETS.len() + 1)) {
+                    Some(preset) => {
+                        self.args.preset = preset.clone();
+                        self.args.cols = None;
+                        self.args.rows = None;
+                    }
+                    None => {
+                        let (cols, rows) = self.args.preset.to_size();
+                        self.args.cols = Some(cols);
+                        self.args.rows = Some(rows);
+                    }
+                }
+            }
+            Item::Columns => {
+                self.args.cols = Some(add(self.args.cols.unwrap_or(1), 1, MAX_SIZE));
+            }
+            Item::Rows => {
+                self.args.rows = Some(add(self.args.rows.unwrap_or(1), 1, MAX_SIZE));
+            }
+            Item::MinePercentage => {
+                self.args.mine_percentage = add(self.args.mine_percentage as u64, 1, 99) as u8;
+            }
+            Item::Theme => {
+                let idx = self
+                    .themes
+                    .iter()
+                    .position(|theme| *theme == self.args.theme)
+                    .unwrap_or(0);
+                self.args.theme = self.themes[step(idx, self.themes.len())].clone();
+            }
+            Item::Glyphs => {
+                let idx = GLYPH_SETS
+                    .iter()
+                    .position(|set| *set == self.args.glyphs)
+                    .unwrap_or(0);
+                self.args.glyphs = GLYPH_SETS[step(idx, GLYPH_SETS.len())];
+            }
+            Item::AssistedOpening => self.args.assisted_opening = !self.args.assisted_opening,
+            Item::AssistedFlagging => self.args.assisted_flagging = !self.args.assisted_flagging,
+            Item::AutoSolve => self.args.auto_solve = !self.args.auto_solve,
+            Item::Chord => {
+                let idx = CHORD_MODES
+                    .iter()
+                    .position(|mode| *mode == self.args.chord)
+                    .unwrap_or(0);
+                self.args.chord = CHORD_MODES[step(idx, CHORD_MODES.len())];
+            }
+            Item::FlagLimit => {
+                self.args.flag_limit = match self.args.flag_limit {
+                    FlagLimit::On => FlagLimit::Off,
+                    FlagLimit::Off => FlagLimit::On,
+                }
+            }
+            Item::Start | Item::Resume | Item::Stats | Item::Quit => {}
+        }
+    }
+
+    /// Types `digit` at the end of the number of `item`, or deletes its last digit if `digit` is None
+    fn edit_number(&mut self, item: Item, digit: Option<u64>) {
+        let edit = |value: u64, min: u64, max: u64| {
+            let value = match digit {
+                Some(digit) if value * 10 + digit <= max => value * 10 + digit,
+                Some(digit) => digit,
+                None => value / 10,
+            };
+            value.clamp(min, max)
+        };
+        match item {
+            Item::Columns => self.args.cols = Some(edit(self.args.cols.unwrap_or(0), 1, MAX_SIZE)),
+            Item::Rows => self.args.rows = Some(edit(self.args.rows.unwrap_or(0), 1, MAX_SIZE)),
+            Item::MinePercentage => {
+                self.args.mine_percentage = edit(self.args.mine_percentage as u64, 1, 99) as u8;
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&self, stdout: &mut impl Write, items: &[Item]) -> Result<()> {
+        write!(
+            stdout,
+            "{}{}mnswpr\r\n\r\n",
+            termion::clear::All,
+            Goto(1, 1)
+        )?;
+        for (idx, item) in items.iter().enumerate() {
+            if idx == self.selected {
+                write!(
+                    stdout,
+                    "> {}{}{}\r\n",
+                    style::Invert,
+                    self.label(*item),
+                    style::Reset
+                )?;
+            } else {
+                write!(stdout, "  {}\r\n", self.label(*item))?;
+            }
+        }
+        write!(
+            stdout,
+            "\r\nup/down to select, left/right to change, digits to type numbers, <enter> to choose, q to quit\r\n{}",
+            self.message.as_deref().unwrap_or_default()
+        )?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+/// Shows the menu until the player quits. Games are started from the menu, and go back to it once they end
+pub fn run(
+    stdout: &mut HideCursor<RawTerminal<Stdout>>,
+    input: &Input,
+    args: &mut Args,
+) -> Result<()> {
+    let mut themes = vec![Theme::Mnswpr, Theme::OG];
+    themes.extend(
+        theme::user_themes()
+            .into_iter()
+            .map(|(_, path)| Theme::Custom(path.to_string_lossy().to_string())),
+    );
+    if !themes.contains(&args.theme) {
+        themes.push(args.theme.clone());
+    }
+
+    let mut menu = Menu {
+        args,
+        mode: Mode::Classic,
+        themes,
+        selected: 0,
+        message: None,
+    };
+
+    loop {
+        let has_saves = !save::list().is_empty();
+        let items = menu.items(has_saves);
+        menu.selected = menu.selected.min(items.len() - 1);
+        menu.draw(stdout, &items)?;
+
+        let Some(key) = input.keys().next() else {
+            return Ok(());
+        };
+        menu.message = None;
+        let item = items[menu.selected];
+
+        let played = match key? {
+            Key::Char('q' | 'Q') | Key::Esc => return Ok(()),
+            Key::Up | Key::Char('w' | 'k') => {
+                menu.selected = (menu.selected + items.len() - 1) % items.len();
+                continue;
+            }
+            Key::Down | Key::Char('s' | 'j') => {
+                menu.selected = (menu.selected + 1) % items.len();
+                continue;
+            }
+            Key::Left | Key::Char('a' | 'h') => {
+                menu.change(item, false);
+                continue;
+            }
+            Key::Right | Key::Char('d' | 'l') => {
+                menu.change(item, true);
+                continue;
+            }
+            Key::Char(c) if c.is_ascii_digit() => {
+                menu.edit_number(item, c.to_digit(10).map(u64::from));
+                continue;
+            }
+            Key::Backspace => {
+                menu.edit_number(item, None);
+                continue;
+            }
+            Key::Char('\n' | ' ') => match item {
+                Item::Quit => return Ok(()),
+                Item::Start => match menu.mode {
+                    Mode::Classic => play_classic(menu.args, stdout, input, None),
+                    Mode::Puzzle => play_puzzles(menu.args, Vec::new(), stdout, input),
+                },
+                Item::Resume => match choose_save(stdout, input)? {
+                    // The save is deleted only once the game is over, or saved again
+                    Some((path, save)) => play_classic(menu.args, stdout, input, Some(save))
+                        .and_then(|_| save::remove(&path)),
+                    None => continue,
+                },
+                Item::Stats => show_stats(stdout, input),
+                item => {
+                    menu.change(item, true);
+                    continue;
+                }
+            },
+            _ => continue,
+        };
+        if let Err(e) = played {
+            menu.message = Some(format!("{e:#}"));
+        }
+    }
+}
+
+/// Returns how long ago `timestamp` was, e.g. "3 hours ago"
+fn ago(timestamp: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let seconds = now.saturating_sub(timestamp);
+    let (amount, unit) = match seconds {
+        0..=59 => return String::from("just now"),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86399 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    format!("{amount} {unit}{plural} ago")
+}
+
+/// Lets the player pick one of the saved games. Returns None if they go back to the menu instead
+fn choose_save(
+    stdout: &mut HideCursor<RawTerminal<Stdout>>,
+    input: &Input,
+) -> Result<Option<(PathBuf, Save)>> {
+    let mut saves = save::list();
+    let mut selected = 0;
+
+    loop {
+        if saves.is_empty() {
+            return Ok(None);
+        }
+        write!(
+            stdout,
+            "{}{}Saved games\r\n\r\n",
+            termion::clear::All,
+            Goto(1, 1)
+        )?;
+        for (idx, (_, save)) in saves.iter().enumerate() {
+            let replay = &save.replay;
+            let (open, empty) = save.progress();
+            let line = format!(
+                "{}x{}, {} mines, {open}/{empty} cells open, saved {}",
+                replay.cols,
+                replay.rows,
+                replay.mines.len(),
+                ago(save.timestamp)
+            );
+            if idx == selected {
+                write!(stdout, "> {}{line}{}\r\n", style::Invert, style::Reset)?;
+            } else {
+                write!(stdout, "  {line}\r\n")?;
+            }
+        }
+        write!(
+            stdout,
+            "\r\nup/down to select, <enter> to resume, q to go back\r\n"
+        )?;
+        stdout.flush()?;
+
+        let Some(key) = input.keys().next() else {
+            return Ok(None);
+        };
+        match key? {
+            Key::Char('q' | 'Q') | Key::Esc => return Ok(None),
+            Key::Up | Key::Char('w' | 'k') => {
+                selected = (selected + saves.len() - 1) % saves.len();
+            }
+            Key::Down | Key::Char('s' | 'j') => selected = (selected + 1) % saves.len(),
+            Key::Char('\n' | ' ') => return Ok(Some(saves.remove(selected))),
+            _ => {}
+        }
+    }
+}
+
+/// Shows how many games were played, won and abandoned, in total and for each size of the field,
+/// until a key is pressed
+fn show_stats(stdout: &mut HideCursor<RawTerminal<Stdout>>, input: &Input) -> Result<()> {
+    let stats = Stats::load()?;
+    let count = |games: &[&GameRecord], result: GameResult| {
+        games.iter().filter(|game| game.result == result).count()
+    };
+    let won = |games: &[&GameRecord]| count(games, GameResult::Won);
+    let percentage = |part: usize, total: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            part as f64 * 100.0 / total as f64
+        }
+    };
+
+    let all: Vec<_> = stats.games.iter().collect();
+    write!(stdout, "{}{}Stats\r\n\r\n", termion::clear::All, Goto(1, 1))?;
+    write!(
+        stdout,
+        "Games played: {}    Won: {} ({:.1}%)    Abandoned: {}\r\n",
+        all.len(),
+        won(&all),
+        percentage(won(&all), all.len()),
+        count(&all, GameResult::Abandoned)
+    )?;
+    let pauses: usize = all.iter().map(|game| game.pauses).sum();
+    if pauses > 0 {
+        let paused_seconds: f64 = all.iter().map(|game| game.paused_seconds).sum();
+        write!(stdout, "Pauses: {pauses} ({paused_seconds:.0}s)\r\n")?;
+    }
+    if !stats.daily.is_empty() {
+        let cleared = stats
+            .daily
+            .iter()
+            .filter(|record| record.result == GameResult::Won)
+            .count();
+        write!(
+            stdout,
+            "Daily challenges: {} played, {cleared} cleared\r\n",
+            stats.daily.len()
+        )?;
+    }
+
+    // Assisted games rank apart from the others of the same size. The mine count is left out,
+    // since it varies from one random board to the next even with the same mine percentage
+    let mut by_size: BTreeMap<(usize, usize, AssistLevel), Vec<_>> = BTreeMap::new();
+    for game in &stats.games {
+        by_size
+            .entry((game.cols, game.rows, game.assist))
+            .or_default()
+            .push(game);
+    }
+    if !by_size.is_empty() {
+        write!(stdout, "\r\n")?;
+    }
+    let available_lines = (termion::terminal_size()?.1 as usize).saturating_sub(7);
+    for ((cols, rows, assist), games) in by_size.iter().take(available_lines) {
+        let assist = match assist {
+            AssistLevel::None => String::new(),
+            assist => format!(", {assist}"),
+        };
+        let mines = games.iter().map(|game| game.mines).sum::<usize>() as f64 / games.len() as f64;
+        write!(
+            stdout,
+            "{cols}x{rows}{assist}: {} games, {mines:.0} mines on average, {} won ({:.1}%)\r\n",
+            games.len(),
+            won(games),
+            percentage(won(games), games.len())
+        )?;
+    }
+    write!(stdout, "\r\nPress any key to go back\r\n")?;
+    stdout.flush()?;
+
+    if let Some(key) = input.keys().next() {
+        key?;
+    }
+    Ok(())
+}
@@ -1,13 +1,75 @@
-use termion::cursor::HideCursor;
+use rand::rngs::StdRng;
+use rand::{random, SeedableRng};
+use termion::cursor::{Goto, HideCursor};
 use termion::event::{Event, Key};
-use termion::input::TermRead;
 use termion::raw::RawTerminal;
 
-use std::io::{stdin, Stdout, Write};
+use std::io::{Stdout, Write};
 use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
 
+use crate::cell::State;
 use crate::colors::{Palette, BG_RESET, FG_RESET};
+use crate::config::{ChordMode, SizePreset};
+use crate::coop::Coop;
 use crate::field::Field;
+use crate::input::{Input, Wait};
+use crate::render::Renderer;
+use crate::replay::{Event as GameEvent, Replay};
+use crate::solver::{self, DifficultyBand, Solver};
+use crate::spectate::{Message, Spectators, Watch};
+use crate::stats::AssistLevel;
+use crate::theme::ThemeCycle;
+use crate::versus::{RaceResult, Versus};
+
+/// How often the file of the theme is checked for changes, when it is watched
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+/// How long the neighbours of a number stay inverted after a chord on it that could not be done
+const FLASH_DURATION: Duration = Duration::from_millis(300);
+/// How often the messages of the other players are checked during a game played over the network
+const NET_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Measures the play time of a game, which does not include the pauses
+#[derive(Default)]
+pub struct Timer {
+    /// The time measured before the timer was last started
+    elapsed: Duration,
+    /// When the timer was last started, if it is running
+    started: Option<Instant>,
+}
+
+impl Timer {
+    /// Starts a timer that already measured `elapsed`, without running it
+    pub fn from_elapsed(elapsed: Duration) -> Self {
+        Self {
+            elapsed,
+            started: None,
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.started.is_none() {
+            self.started = Some(Instant::now());
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(started) = self.started.take() {
+            self.elapsed += started.elapsed();
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+            + self
+                .started
+                .map_or(Duration::ZERO, |started| started.elapsed())
+    }
+}
 
 pub struct Cursor {
     pub row: usize,
@@ -21,6 +83,21 @@ pub enum Direction {
     Down,
 }
 
+/// How a single game ended
+pub enum Outcome {
+    Won,
+    Lost,
+    /// The player opened a cell that could not be proven safe while guessing was not allowed
+    Guessed,
+    Quit,
+    /// The player abandoned the board to play a new one
+    NewGame,
+    /// The player abandoned the board to play it again from the start
+    Restart,
+    /// The race against another player was decided before the player finished the board
+    RaceOver,
+}
+
 pub struct Mnswpr {
     pub field: Field,
     pub cursor: Cursor,
@@ -28,10 +105,58 @@ pub struct Mnswpr {
     pub cols: usize,
     mine_percentage: u8,
     palette: Palette,
+    /// If present, every game starts from this field instead of a randomized one
+    initial_field: Option<Field>,
+    /// If active, opening a cell that cannot be logically proven safe ends the game
+    pub no_guess: bool,
+    /// If present, randomized fields are generated again until their difficulty falls in the band
+    pub difficulty: Option<DifficultyBand>,
+    /// If active, opening an open cell chords on it, like the chord key does
+    pub assisted_opening: bool,
+    /// What chording on a number does
+    pub chord_mode: ChordMode,
+    /// If active, every flag and open forced by a single number is done after each move, across the whole board
+    pub auto_solve: bool,
+    /// If active, flagging an open cell with as many non-open neighbours as neighbouring mines flags its closed neighbours
+    pub assisted_flagging: bool,
+    /// If active, more flags than mines can be placed
+    pub unlimited_flags: bool,
+    /// If present, every randomized field is generated from this seed instead of a random one
+    pub seed: Option<u64>,
+    /// The seed the field of the current game gets generated from
+    game_seed: u64,
+    /// Whether the mines of the randomized field of the current game are placed
+    generated: bool,
+    /// The size preset the field was made from, if any. Only used to describe the game on the status line
+    pub preset: Option<SizePreset>,
+    /// The starting field of the current game and everything the player did to it
+    pub replay: Replay,
+    renderer: Renderer,
+    /// The play time of the current game. It starts with the first move
+    pub timer: Timer,
+    /// When the current pause started, if the game is paused
+    paused_at: Option<Instant>,
+    /// If present, the themes that can be switched between with `t`
+    pub themes: Option<ThemeCycle>,
+    /// If present, the game is a race against another player
+    pub versus: Option<Versus>,
+    /// If present, the game is played together with other players, on the board of the host
+    pub coop: Option<Coop>,
+    /// If present, the games are published to the spectators watching them with `mnswpr watch`
+    pub spectators: Option<Spectators>,
+    /// If present, the game is the daily challenge described by this text, e.g. its date
+    pub daily: Option<String>,
+    /// Whether this is the game of another player, shown read-only with `mnswpr watch`
+    watching: bool,
+    /// Shown under the status line until the next key is pressed
+    message: Option<String>,
+    /// The cells drawn inverted after a chord that could not be done, until the given instant
+    flash: Option<(Vec<(usize, usize)>, Instant)>,
 }
 
 impl Mnswpr {
     pub fn new(rows: usize, cols: usize, mine_percentage: u8, palette: Palette) -> Self {
+        let renderer = Renderer::new(palette.glyphs.cell_width());
         Self {
             cursor: Cursor { row: 0, col: 0 },
             field: Field::new(rows, cols),
@@ -39,82 +164,654 @@ impl Mnswpr {
             cols,
             mine_percentage,
             palette,
+            initial_field: None,
+            no_guess: false,
+            difficulty: None,
+            assisted_opening: false,
+            chord_mode: ChordMode::Classic,
+            auto_solve: false,
+            assisted_flagging: false,
+            unlimited_flags: false,
+            seed: None,
+            game_seed: 0,
+            generated: false,
+            preset: None,
+            replay: Replay::default(),
+            renderer,
+            timer: Timer::default(),
+            paused_at: None,
+            themes: None,
+            versus: None,
+            coop: None,
+            spectators: None,
+            daily: None,
+            watching: false,
+            message: None,
+            flash: None,
         }
     }
 
-    /// Randomizes the field keeping a safe area around the current position of the cursor
-    pub fn randomize_field(&mut self) {
-        self.field
-            .randomize(self.mine_percentage, self.cursor.row, self.cursor.col);
+    /// Constructs a game that always starts from `field`, which may already have some open cells
+    pub fn from_field(field: Field, palette: Palette) -> Self {
+        let renderer = Renderer::new(palette.glyphs.cell_width());
+        Self {
+            cursor: Cursor { row: 0, col: 0 },
+            rows: field.rows,
+            cols: field.cols,
+            field: field.clone(),
+            mine_percentage: 0,
+            palette,
+            initial_field: Some(field),
+            no_guess: false,
+            difficulty: None,
+            assisted_opening: false,
+            chord_mode: ChordMode::Classic,
+            auto_solve: false,
+            assisted_flagging: false,
+            unlimited_flags: false,
+            seed: None,
+            game_seed: 0,
+            generated: false,
+            preset: None,
+            replay: Replay::default(),
+            renderer,
+            timer: Timer::default(),
+            paused_at: None,
+            themes: None,
+            versus: None,
+            coop: None,
+            spectators: None,
+            daily: None,
+            watching: false,
+            message: None,
+            flash: None,
+        }
     }
 
-    /// Prints the field with the current palette.
-    /// May return an error if it was not able to write in `f`
-    pub fn print_field(&self, f: &mut impl Write) -> anyhow::Result<()> {
-        let mut str_repr = String::with_capacity(self.rows * self.cols * 3 * 2);
+    /// Resets the field to the initial field if there is one, otherwise to an empty field
+    /// that will be generated from a new seed
+    pub fn reset(&mut self) {
+        match &self.initial_field {
+            Some(field) => self.field = field.clone(),
+            None => {
+                self.field.reset();
+                self.game_seed = self.seed.unwrap_or_else(random);
+            }
+        }
+        self.field.unlimited_flags = self.unlimited_flags;
+        self.generated = false;
+        self.replay = Replay::new(&self.field);
+        if self.initial_field.is_none() {
+            // Known before the field is generated, so that spectators see it from the start
+            self.replay.seed = Some(self.game_seed);
+        }
+        self.timer = Timer::default();
+        self.paused_at = None;
+        self.flash = None;
+        self.renderer.invalidate();
+    }
 
-        for row in 0..self.rows {
-            for col in 0..self.cols {
-                let cell = self.get_unchecked(row, col);
+    /// Whether the mines of the current game are placed. Puzzles start with them,
+    /// while randomized fields get them on the first move
+    fn is_generated(&self) -> bool {
+        self.initial_field.is_some() || self.generated
+    }
+
+    /// Starts the current game again with the same mines, with every cell closed.
+    /// The cursor goes back to the first cell that was opened, whose neighbours are known to be safe
+    pub fn restart(&mut self) {
+        if self.initial_field.is_some() || !self.is_generated() {
+            self.reset();
+            return;
+        }
+
+        let first_open = self
+            .replay
+            .events
+            .iter()
+            .find(|event| event.is_uncover())
+            .and_then(|event| event.position());
+        if let Some((row, col)) = first_open {
+            self.cursor = Cursor { row, col };
+        }
+
+        self.field.restart();
+        self.replay.events.clear();
+        self.timer = Timer::default();
+        self.paused_at = None;
+        self.flash = None;
+        self.renderer.invalidate();
+    }
+
+    /// Continues the game recorded in `replay`, from where its moves left the field after `elapsed` of play time
+    pub fn resume(&mut self, replay: Replay, elapsed: Duration) {
+        let mut field = replay.initial_field();
+        for event in &replay.events {
+            event.apply(&mut field);
+        }
+        self.rows = field.rows;
+        self.cols = field.cols;
+        self.field = field;
+        self.game_seed = replay.seed.unwrap_or_default();
+        self.generated = true;
+        let (row, col) = replay
+            .events
+            .iter()
+            .rev()
+            .find_map(|event| event.position())
+            .unwrap_or((0, 0));
+        self.cursor = Cursor { row, col };
+        self.replay = replay;
+        self.timer = Timer::from_elapsed(elapsed);
+        self.paused_at = None;
+        self.flash = None;
+        self.renderer.invalidate();
+    }
+
+    /// Applies `event` to the field and records it in the replay. Returns whether a mine was uncovered.
+    /// In a co-op game, the moves of the players other than the host are only applied once the host sends them back
+    fn apply(&mut self, event: GameEvent) -> bool {
+        if let Some(coop) = &mut self.coop {
+            if !coop.share_move(event) {
+                return false;
+            }
+        }
+        self.record(event)
+    }
+
+    /// Applies `event` to the field and records it in the replay, without sharing it with the other players.
+    /// Returns whether a mine was uncovered
+    fn record(&mut self, event: GameEvent) -> bool {
+        self.timer.start();
+        self.replay.events.push(event);
+        event.apply(&mut self.field)
+    }
+
+    /// Returns whether opening the cells at `positions` would be a guess,
+    /// that is whether any of the closed ones cannot be proven safe from what is visible on the board
+    fn is_guess(&self, positions: &[(usize, usize)]) -> bool {
+        let mut solver = Solver::new(&self.field);
+        solver.solve();
+
+        positions.iter().any(|&(row, col)| {
+            self.get(row, col).is_some_and(|cell| cell.is_closed()) && !solver.is_safe(row, col)
+        })
+    }
+
+    /// Returns how much the game helps the player, which is recorded with its result
+    pub fn assist_level(&self) -> AssistLevel {
+        if self.auto_solve {
+            AssistLevel::AutoTrivial
+        } else if self.assisted_flagging || self.chord_mode == ChordMode::Assisted {
+            // Assisted opening only binds the open keys to the chord, so it is no assist of its own
+            AssistLevel::Cursor
+        } else {
+            AssistLevel::None
+        }
+    }
+
+    /// Opens every closed cell and flags every closed mine that a single number proves to be so,
+    /// repeating with the numbers this uncovers until nothing else is forced. Every move is recorded in the replay.
+    /// Mines are only flagged while flags can be placed, and questioned cells are only opened
+    fn auto_solve_trivial(&mut self) {
+        loop {
+            let mut solver = Solver::new(&self.field);
+            solver.solve_trivial();
+            let (safe_cells, mine_cells) = (solver.safe_cells(), solver.mine_cells());
+
+            let mut progress = false;
+            for (row, col) in safe_cells {
+                // An earlier open may already have uncovered it
+                if self.get_unchecked(row, col).is_closed() {
+                    self.apply(GameEvent::Open { row, col });
+                    progress = true;
+                }
+            }
+            for (row, col) in mine_cells {
+                if matches!(self.get_unchecked(row, col).state, State::Closed) && self.can_flag() {
+                    self.apply(GameEvent::Flag { row, col });
+                    progress = true;
+                }
+            }
+
+            if !progress {
+                return;
+            }
+        }
+    }
 
-                let cell_repr = cell.to_string_with_palette(
-                    &self.palette,
-                    self.cursor.row == row && self.cursor.col == col,
+    /// Chords on the cell at (row, col) as `self.chord_mode` says.
+    /// When the flags around the number do not match it, its non-open neighbours flash instead.
+    /// Returns how the game ended, if the chord ended it
+    fn chord(&mut self, row: usize, col: usize) -> Option<Outcome> {
+        let cell = *self.get_unchecked(row, col);
+        if !cell.is_open() {
+            self.message = Some(String::from("Can't chord: the cell is not open"));
+            return None;
+        }
+
+        let keep_questioned = self.chord_mode == ChordMode::Classic;
+        let flagged = self
+            .get_flagged_nbors_amt(row, col)
+            .expect("Position out of bounds");
+        let non_open: Vec<(usize, usize)> = self
+            .neighbours(row, col)
+            .into_iter()
+            .filter(|&(r, c)| !self.get_unchecked(r, c).is_open())
+            .collect();
+
+        if flagged == cell.neighbouring_bomb_count {
+            let to_open: Vec<(usize, usize)> = non_open
+                .into_iter()
+                .filter(|&(r, c)| {
+                    let cell = self.get_unchecked(r, c);
+                    cell.is_closed() && !(keep_questioned && cell.is_questioned())
+                })
+                .collect();
+            if to_open.is_empty() {
+                return None;
+            }
+            if self.no_guess && self.is_guess(&to_open) {
+                return Some(Outcome::Guessed);
+            }
+            return self
+                .apply(GameEvent::Chord {
+                    row,
+                    col,
+                    keep_questioned,
+                })
+                .then_some(Outcome::Lost);
+        }
+
+        if self.chord_mode == ChordMode::Assisted
+            && flagged < cell.neighbouring_bomb_count
+            && non_open.len() == cell.neighbouring_bomb_count
+        {
+            self.apply(GameEvent::FlagAround { row, col });
+            return None;
+        }
+
+        self.message = Some(format!(
+            "Can't chord: {flagged} flagged around a {}",
+            cell.neighbouring_bomb_count
+        ));
+        self.flash = Some((non_open, Instant::now() + FLASH_DURATION));
+        None
+    }
+
+    /// Randomizes the field from the seed of the game keeping a safe area around the current position of the cursor.
+    /// With a difficulty band, fields are generated again until one gets rated in the band, showing the progress
+    /// on the message line. Returns an error if none could be found in time
+    pub fn randomize_field(&mut self, f: &mut impl Write) -> anyhow::Result<()> {
+        const MAX_ATTEMPTS: usize = 1000;
+        const TIME_LIMIT: Duration = Duration::from_secs(10);
+        const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        let mut rng = StdRng::seed_from_u64(self.game_seed);
+        let start = Instant::now();
+        let mut shown = start;
+        let mut rating = None;
+
+        for attempt in 1.. {
+            self.field
+                .randomize_with(&mut rng, self.mine_percentage, row, col);
+            let Some(band) = self.difficulty else {
+                break;
+            };
+            let candidate = solver::rate(&self.field, row, col);
+            if band.contains(candidate.difficulty) {
+                rating = Some(candidate);
+                break;
+            }
+
+            if attempt == MAX_ATTEMPTS || start.elapsed() >= TIME_LIMIT {
+                self.message = None;
+                anyhow::bail!(
+                    "Could not generate a field in the requested difficulty band in {attempt} attempts ({:.1}s), \
+                     try changing the mine percentage",
+                    start.elapsed().as_secs_f64()
                 );
-                str_repr.push_str(&cell_repr);
             }
-            str_repr = format!("{str_repr}{BG_RESET}{FG_RESET}\r\n");
+            if shown.elapsed() >= PROGRESS_INTERVAL {
+                shown = Instant::now();
+                self.message = Some(format!(
+                    "Looking for a board in the difficulty band: {attempt} tried in {:.1}s",
+                    start.elapsed().as_secs_f64()
+                ));
+                self.print_game_state(f, false)?;
+            }
         }
 
-        write!(f, "{str_repr}")?;
+        self.message = None;
+        self.generated = true;
+        self.replay = Replay::new(&self.field);
+        self.replay.rating = rating;
+        self.replay.seed = Some(self.game_seed);
         Ok(())
     }
 
-    /// Prints the field with the current palette, highlighting the flags that were placed in the right
-    /// place and the ones that are placed on empty cells
-    /// May return an error if it was not able to write in `f`
-    pub fn print_field_game_lost(&self, f: &mut impl Write) -> anyhow::Result<()> {
-        let mut str_repr = String::with_capacity(self.rows * self.cols * 3 * 2);
+    /// Rates the randomized field of the current game from its first move, unless it was rated while generating it.
+    /// Done once the game is over rather than on the first move, so that generating a field stays instant
+    fn rate_field(&mut self) {
+        if self.initial_field.is_some() || !self.generated || self.replay.rating.is_some() {
+            return;
+        }
+        let first_open = self
+            .replay
+            .events
+            .iter()
+            .find(|event| event.is_uncover())
+            .and_then(|event| event.position());
+        if let Some((row, col)) = first_open {
+            let field = self.replay.initial_field();
+            self.replay.rating = Some(solver::rate(&field, row, col));
+        }
+    }
+
+    /// Returns the representation of every cell of the field with the current palette, row by row.
+    /// If `open_everything == true`, the cells are shown open, with the status of the flags
+    /// (placed correctly on a mine or placed on an empty cell).
+    /// While the game is paused every cell is blank, so the board can't be studied without the timer running
+    fn cell_reprs(&self, open_everything: bool) -> Vec<String> {
+        if self.is_paused() && !open_everything {
+            let blank = format!("{BG_RESET}{}", " ".repeat(self.palette.glyphs.cell_width()));
+            return vec![blank; self.rows * self.cols];
+        }
+
+        let mut reprs = Vec::with_capacity(self.rows * self.cols);
 
         for row in 0..self.rows {
             for col in 0..self.cols {
                 let cell = self.get_unchecked(row, col);
+                let with_cursor = self.cursor.row == row && self.cursor.col == col;
+                let flashing = self
+                    .flash
+                    .as_ref()
+                    .is_some_and(|(cells, _)| cells.contains(&(row, col)));
 
-                let cell_repr = cell.to_string_with_palette_lost(
-                    &self.palette,
-                    self.cursor.row == row && self.cursor.col == col,
-                );
-                str_repr.push_str(&cell_repr);
+                // The end screen shows what every cell hides, and only the cursor of this player
+                reprs.push(if open_everything {
+                    cell.to_string_with_palette_lost(&self.palette, with_cursor)
+                } else if let Some(player) = self.player_at(row, col, with_cursor) {
+                    let cursors = &self.palette.player_cursor_fg;
+                    cell.to_string_with_player_cursor(
+                        &self.palette,
+                        &cursors[player % cursors.len()],
+                    )
+                } else if flashing {
+                    cell.to_string_with_palette_flashing(&self.palette, with_cursor)
+                } else {
+                    cell.to_string_with_palette(&self.palette, with_cursor)
+                });
             }
-            str_repr = format!("{str_repr}{BG_RESET}{FG_RESET}\r\n");
         }
+        reprs
+    }
+
+    /// Returns the co-op player whose cursor is on the cell at (row, col), if there is one.
+    /// The cursor of this player, on the cell if `with_cursor`, is drawn over the others
+    fn player_at(&self, row: usize, col: usize, with_cursor: bool) -> Option<usize> {
+        let coop = self.coop.as_ref()?;
+        if with_cursor {
+            return Some(coop.player());
+        }
+        coop.cursors()
+            .find(|&(_, cursor)| cursor == (row, col))
+            .map(|(player, _)| player)
+    }
+
+    /// Returns the line shown under the board, describing the game and the active modes
+    fn status_line(&self) -> String {
+        let mut parts = Vec::new();
+        match &self.preset {
+            Some(preset) => parts.push(format!("Preset: {preset}")),
+            None => parts.push(format!("Size: {}x{}", self.cols, self.rows)),
+        }
+        // Boards that were not generated from a seed, like the daily ones seen by a spectator, have none to show
+        if self.initial_field.is_none() && self.replay.seed.is_some() {
+            parts.push(format!("Seed: {}", self.game_seed));
+        }
+        if self.versus.is_some() {
+            parts.push(String::from("Versus"));
+        }
+        if let Some(daily) = &self.daily {
+            parts.push(format!("Daily: {daily}"));
+        }
+        if let Some(coop) = &self.coop {
+            parts.push(format!(
+                "Co-op: player {} of {}",
+                coop.player() + 1,
+                coop.player_count()
+            ));
+        }
+        if self.no_guess {
+            parts.push(String::from("No guessing"));
+        }
+        if self.assisted_opening {
+            parts.push(String::from("Assisted opening"));
+        }
+        if self.chord_mode != ChordMode::Classic {
+            parts.push(format!("Chord: {}", self.chord_mode));
+        }
+        if self.auto_solve {
+            parts.push(String::from("Auto-solve trivial"));
+        }
+        if self.assisted_flagging {
+            parts.push(String::from("Assisted flagging"));
+        }
+        if self.unlimited_flags {
+            parts.push(String::from("Unlimited flags"));
+        }
+        if self.watching {
+            parts.push(String::from("Watching, q: stop"));
+        } else {
+            parts.push(String::from("?: help"));
+        }
+        parts.join("    ")
+    }
+
+    /// Returns the keys that do something in the current game, each with a description of what it does
+    fn help(&self) -> Vec<(&'static str, &'static str)> {
+        let mut help = vec![
+            ("wasd hjkl arrows", "Move the cursor"),
+            ("<space> <enter>", "Open the cell under the cursor"),
+        ];
+        if self.assisted_opening {
+            help.push(("", "On an open number, chord like c does"));
+        }
+        help.push((
+            "c",
+            match self.chord_mode {
+                ChordMode::Classic => {
+                    "On a number with all its mines flagged, open every other neighbour but the ?"
+                }
+                ChordMode::Assisted => {
+                    "On a number with all its mines flagged, open every other neighbour, else flag them if they must be mines"
+                }
+                ChordMode::All => {
+                    "On a number with all its mines flagged, open every other neighbour"
+                }
+            },
+        ));
+        help.push((
+            "f",
+            "Flag the cell under the cursor, again to mark it with ?, again to clear it",
+        ));
+        if self.assisted_flagging {
+            help.push((
+                "",
+                "On a number with as many closed neighbours as mines, flag all of them",
+            ));
+        }
+        if self.themes.is_some() {
+            help.push(("t", "Switch to the next theme"));
+        }
+        // A game with other players can't be paused
+        if !self.is_networked() {
+            help.push((
+                "p",
+                "Pause the game, hiding the board and stopping the timer",
+            ));
+        }
+        if self.can_leave_board() {
+            help.push(("n", "Abandon this board and play a new one"));
+            help.push(("r", "Play this board again from the start"));
+        }
+        help.push(("?", "Show this help"));
+        help.push(("q", "Quit the game"));
+        help
+    }
+
+    /// Draws the help over the board, waits for a key and draws the game again
+    fn show_help(&mut self, f: &mut impl Write, input: &Input) -> anyhow::Result<()> {
+        let help = self.help();
+        let keys_width = help.iter().map(|(keys, _)| keys.len()).max().unwrap_or(0);
+        let mut lines = vec![String::from("Keys"), String::new()];
+        lines.extend(
+            help.iter()
+                .map(|(keys, action)| format!("{keys:keys_width$}  {action}")),
+        );
+        lines.push(String::new());
+        lines.push(String::from("Press any key to go back"));
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+
+        let border = format!("+{}+", "-".repeat(width + 2));
+        let mut out = format!("{BG_RESET}{FG_RESET}{}{border}", Goto(2, 2));
+        for (idx, line) in lines.iter().enumerate() {
+            out.push_str(&format!("{}| {line:width$} |", Goto(2, idx as u16 + 3)));
+        }
+        out.push_str(&format!("{}{border}", Goto(2, lines.len() as u16 + 3)));
+        write!(f, "{out}")?;
+        f.flush()?;
 
-        write!(f, "{str_repr}")?;
+        if let Some(key) = input.keys().next() {
+            key?;
+        }
+        write!(f, "{}", termion::clear::All)?;
+        self.renderer.invalidate();
         Ok(())
     }
 
+    /// Returns whether the game is played with other players, over the network
+    fn is_networked(&self) -> bool {
+        self.versus.is_some() || self.coop.is_some()
+    }
+
+    /// Returns whether the board can be left for a new one or played again from the start.
+    /// A game with other players or a daily challenge has a single attempt
+    fn can_leave_board(&self) -> bool {
+        !self.is_networked() && self.daily.is_none()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Pauses the game if it is running, or continues it if it is paused. Both are recorded in the replay
+    fn toggle_pause(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.replay.events.push(GameEvent::Unpause {
+                seconds: paused_at.elapsed().as_secs_f64(),
+            });
+            self.timer.start();
+        } else if self.timer.is_running() {
+            self.timer.stop();
+            self.paused_at = Some(Instant::now());
+            self.replay.events.push(GameEvent::Pause);
+        } else {
+            self.message = Some(String::from(
+                "Nothing to pause: the timer starts with the first move",
+            ));
+        }
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Switches to `palette`, or shows why it could not be loaded.
+    /// `name` is the name of the theme of `palette`
+    fn change_palette(
+        &mut self,
+        f: &mut impl Write,
+        palette: anyhow::Result<Palette>,
+        name: String,
+    ) -> anyhow::Result<()> {
+        match palette {
+            Ok(palette) if !fits_in_terminal(self.cols, &palette) => {
+                self.message = Some(format!("The theme {name} is too wide for this terminal"));
+            }
+            Ok(palette) => {
+                // The cells may have a different width, so the old frame cannot be reused
+                self.renderer = Renderer::new(palette.glyphs.cell_width());
+                self.palette = palette;
+                write!(f, "{}", termion::clear::All)?;
+                self.message = Some(format!("Theme: {name}"));
+            }
+            Err(e) => self.message = Some(format!("{e:#}")),
+        }
+        self.print_game_state(f, false)
+    }
+
+    /// Makes the next call to `print_game_state` draw the whole game state again.
+    /// Must be called after something else was drawn over the game, e.g. after clearing the screen
+    pub fn invalidate_frame(&mut self) {
+        self.renderer.invalidate();
+    }
+
     /// Prints the game state. Prints header (bomb and flag count)
-    /// Then prints the current state of the board if `open_everything == false`
-    /// otherwise print the open board with the status of the flags (placed correctly on a mine or placed on an empty cell)
+    /// Then prints the current state of the board, the status line and the message if `open_everything == false`
+    /// otherwise print the open board with the status of the flags (placed correctly on a mine or placed on an empty cell).
+    /// Only the parts that changed since the last call get written.
     /// May return an error if it was not able to write in `f`
     pub fn print_game_state(
-        &self,
+        &mut self,
         f: &mut impl Write,
         open_everything: bool,
     ) -> anyhow::Result<()> {
-        write!(
-            f,
-            "{}Mines:{}    Flags:{}\r\n",
-            termion::cursor::Goto(1, 1),
+        // Goes below 0 when more flags than mines are placed
+        let remaining = self.field.mine_count as isize - self.field.flag_count as isize;
+        let mut header = format!(
+            "Mines:{}    Flags:{}    Remaining:{remaining}    Time:{}s",
             self.field.mine_count,
-            self.field.flag_count
-        )?;
+            self.field.flag_count,
+            self.timer.elapsed().as_secs()
+        );
+        if let Some(versus) = &self.versus {
+            header.push_str(&format!("    {}", versus.opponent_bar()));
+        }
+        let cells = self.cell_reprs(open_everything);
+
+        self.renderer.draw(f, header, cells, self.cols)?;
         if !open_everything {
-            self.print_field(f)?;
-        } else {
-            self.print_field_game_lost(f)?;
+            write!(
+                f,
+                "{}{}\r\n{}{}",
+                termion::clear::CurrentLine,
+                self.status_line(),
+                termion::clear::CurrentLine,
+                if self.is_paused() && self.watching {
+                    "Paused"
+                } else if self.is_paused() {
+                    "Paused, press p to continue"
+                } else {
+                    self.message.as_deref().unwrap_or_default()
+                }
+            )?;
         }
         f.flush()?;
+
+        if let Some(spectators) = &mut self.spectators {
+            spectators.publish(
+                &self.field,
+                &self.replay,
+                open_everything,
+                (self.cursor.row, self.cursor.col),
+                self.timer.elapsed(),
+                self.timer.is_running(),
+            );
+        }
         Ok(())
     }
 
@@ -143,71 +840,291 @@ impl Mnswpr {
         }
     }
 
-    /// Handles the game loop for one game
-    /// Returns Some(true) if the user won the current game
-    /// Returns Some(false) if the user lost the current game
-    /// Returns None if the user quit
+    /// Handles the game loop for one game and returns how it ended.
+    /// The timer is stopped when the game ends, so that `self.timer` holds its play time
     pub fn play(
         &mut self,
         stdout: &mut HideCursor<RawTerminal<Stdout>>,
-        assisted_opening: bool,
-        assisted_flagging: bool,
-    ) -> anyhow::Result<Option<bool>> {
-        let stdin = stdin();
+        input: &Input,
+    ) -> anyhow::Result<Outcome> {
+        let outcome = self.play_until_end(stdout, input);
+        self.rate_field();
+        if self.is_paused() {
+            self.toggle_pause();
+        }
+        self.timer.stop();
+        let alive = matches!(outcome, Ok(Outcome::Won | Outcome::RaceOver));
+        self.report_progress(alive);
+        if let (Ok(Outcome::Lost), Some(coop)) = (&outcome, &mut self.coop) {
+            // Unless the mine came from the move of another player, it was uncovered by this one
+            let player = coop.player();
+            coop.mine_uncovered(player);
+        }
+        outcome
+    }
+
+    /// Tells the other player of a race how many cells were opened so far, and whether this player is still playing
+    fn report_progress(&mut self, alive: bool) {
+        let open = self.rows * self.cols - self.field.mine_count - self.field.closed_empty_cells;
+        if let Some(versus) = &mut self.versus {
+            versus.report(open, alive);
+        }
+    }
+
+    /// Shows the board, with the progress of the other player, until the race is decided.
+    /// Returns how it ended, or None if the player left before that
+    pub fn wait_for_race(
+        &mut self,
+        stdout: &mut HideCursor<RawTerminal<Stdout>>,
+        input: &Input,
+    ) -> anyhow::Result<Option<RaceResult>> {
+        self.message = Some(String::from(
+            "Waiting for the other player to finish, press q to leave",
+        ));
+        loop {
+            let Some(versus) = &mut self.versus else {
+                return Ok(None);
+            };
+            versus.poll();
+            if let Some(result) = versus.result() {
+                self.message = None;
+                return Ok(Some(result));
+            }
+
+            self.print_game_state(stdout, false)?;
+            match input.wait(Some(NET_POLL_INTERVAL))? {
+                Wait::Event(Event::Key(Key::Char('q' | 'Q'))) | Wait::Closed => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+
+    /// Applies the moves the other players of a co-op game made since the last call.
+    /// Returns how the game ended, if one of these moves ended it or the host left
+    fn play_shared_moves(&mut self) -> Option<Outcome> {
+        let coop = self.coop.as_mut()?;
+        let moves = coop.poll(&self.replay);
+        if coop.host_left() {
+            return Some(Outcome::Quit);
+        }
+
+        for (player, event) in moves {
+            if self.record(event) {
+                if let Some(coop) = &mut self.coop {
+                    coop.mine_uncovered(player);
+                }
+                return Some(Outcome::Lost);
+            }
+        }
+        (self.field.closed_empty_cells == 0).then_some(Outcome::Won)
+    }
+
+    /// Shows the game published at the other end of `watch`, starting from `start`, until it stops being published
+    /// or q is pressed. Nothing can be done to the game, and the whole board is shown once it is over
+    pub fn watch(
+        &mut self,
+        watch: &Watch,
+        start: Message,
+        stdout: &mut HideCursor<RawTerminal<Stdout>>,
+        input: &Input,
+    ) -> anyhow::Result<()> {
+        self.watching = true;
+        let mut messages = vec![start];
+        let mut over = false;
+        loop {
+            // The screen is cleared when the size of the board changes or a game ends, and the end is announced once
+            let mut cleared = false;
+            let was_over = over;
+            for message in messages {
+                match message {
+                    Message::Board {
+                        view,
+                        seconds,
+                        running,
+                        paused,
+                        row,
+                        col,
+                    } => {
+                        cleared |= (view.rows, view.cols) != (self.rows, self.cols);
+                        over = view.over;
+                        self.rows = view.rows;
+                        self.cols = view.cols;
+                        self.field = view.field();
+                        self.replay = Replay {
+                            rows: view.rows,
+                            cols: view.cols,
+                            seed: view.seed,
+                            ..Default::default()
+                        };
+                        self.cursor = Cursor { row, col };
+                        self.timer = Timer::from_elapsed(Duration::from_secs_f64(seconds));
+                        if running && !paused {
+                            self.timer.start();
+                        }
+                        self.paused_at = paused.then(Instant::now);
+                    }
+                    Message::Cursor { row, col } => self.cursor = Cursor { row, col },
+                }
+            }
 
+            if over {
+                self.timer.stop();
+            }
+            cleared |= over != was_over;
+            if cleared {
+                write!(stdout, "{}", termion::clear::All)?;
+                self.invalidate_frame();
+            }
+            self.print_game_state(stdout, over)?;
+            if over && cleared {
+                write!(
+                    stdout,
+                    "Game over, waiting for the next one. Press q to stop watching"
+                )?;
+                stdout.flush()?;
+            }
+
+            match input.wait(Some(NET_POLL_INTERVAL))? {
+                Wait::Event(Event::Key(Key::Char('q' | 'Q'))) | Wait::Closed => return Ok(()),
+                _ => {}
+            }
+            messages = match watch.poll() {
+                Ok(messages) => messages,
+                // The player stopped publishing, e.g. because they quit
+                Err(_) => return Ok(()),
+            };
+        }
+    }
+
+    fn play_until_end(
+        &mut self,
+        stdout: &mut HideCursor<RawTerminal<Stdout>>,
+        input: &Input,
+    ) -> anyhow::Result<Outcome> {
+        self.message = None;
         self.print_game_state(stdout, false)?;
         stdout.flush()?;
 
-        let mut first_move = true;
+        let mut first_move = !self.is_generated();
+        // The others see where this player starts, before the first key is pressed
+        if let Some(coop) = &mut self.coop {
+            coop.share_cursor(self.cursor.row, self.cursor.col);
+        }
 
-        for e in stdin.events() {
-            let e = e?;
-            if !matches!(e, Event::Key(_)) {
-                continue;
+        loop {
+            if let Some(versus) = &mut self.versus {
+                versus.poll();
+                if versus.result().is_some() {
+                    return Ok(Outcome::RaceOver);
+                }
+            }
+            if let Some(outcome) = self.play_shared_moves() {
+                self.print_game_state(stdout, false)?;
+                return Ok(outcome);
             }
-            let Event::Key(event) = e else { unreachable!(); };
+
+            let watch = self
+                .themes
+                .as_ref()
+                .filter(|themes| themes.watch)
+                .map(|_| WATCH_INTERVAL);
+            // Wakes up when the shown time changes, to keep the header up to date
+            let tick = self.timer.is_running().then(|| {
+                Duration::from_secs(1)
+                    - Duration::from_nanos(self.timer.elapsed().subsec_nanos().into())
+            });
+            let flash = self
+                .flash
+                .as_ref()
+                .map(|(_, until)| until.saturating_duration_since(Instant::now()));
+            let net = self.is_networked().then_some(NET_POLL_INTERVAL);
+            let timeout = [watch, tick, flash, net].into_iter().flatten().min();
+            let e = match input.wait(timeout)? {
+                Wait::Event(e) => e,
+                Wait::Timeout => {
+                    if self
+                        .flash
+                        .as_ref()
+                        .is_some_and(|(_, until)| *until <= Instant::now())
+                    {
+                        self.flash = None;
+                    }
+                    if let Some(themes) = &mut self.themes {
+                        if let Some(palette) = themes.reload() {
+                            let name = format!("{} (reloaded)", themes.current().name());
+                            self.change_palette(stdout, palette, name)?;
+                        }
+                    }
+                    self.print_game_state(stdout, false)?;
+                    continue;
+                }
+                Wait::Closed => break,
+            };
+            let Event::Key(event) = e else {
+                continue;
+            };
+            self.message = None;
 
             let crow = self.cursor.row;
             let ccol = self.cursor.col;
 
             match event {
-                Key::Char('q' | 'Q') => return Ok(None),
+                Key::Char('q' | 'Q') => return Ok(Outcome::Quit),
+                Key::Char('p' | 'P') if !self.is_networked() => self.toggle_pause(),
+                // The board is hidden while paused, nothing else can be done until the game continues
+                _ if self.is_paused() => {}
+                Key::Char('n' | 'N') if self.can_leave_board() => return Ok(Outcome::NewGame),
+                Key::Char('r' | 'R') if self.can_leave_board() => return Ok(Outcome::Restart),
+                Key::Char('?') => self.show_help(stdout, input)?,
+                Key::Char('t' | 'T') => {
+                    if let Some(themes) = &mut self.themes {
+                        let cols = self.cols;
+                        let palette = themes.next(|palette| fits_in_terminal(cols, palette));
+                        let name = themes.current().name();
+                        self.change_palette(stdout, palette, name)?;
+                        continue;
+                    }
+                }
                 Key::Char('w' | 'W' | 'k' | 'K') | Key::Up => self.move_cursor(Direction::Up),
                 Key::Char('a' | 'A' | 'h' | 'H') | Key::Left => self.move_cursor(Direction::Left),
                 Key::Char('s' | 'S' | 'j' | 'J') | Key::Down => self.move_cursor(Direction::Down),
                 Key::Char('d' | 'D' | 'l' | 'L') | Key::Right => self.move_cursor(Direction::Right),
                 Key::Char(' ' | '\n') => {
                     if first_move {
-                        self.randomize_field();
+                        self.randomize_field(stdout)?;
                         first_move = false;
                     }
 
-                    let cell = self.get_unchecked(crow, ccol);
-
-                    if assisted_opening
-                        && cell.is_open()
-                        && self
-                            .get_flagged_nbors_amt(crow, ccol)
-                            .expect("Cursor out of bounds")
-                            == cell.neighbouring_bomb_count
-                    {
-                        let exploded = self
-                            .uncover_around_cell_at(crow, ccol)
-                            .expect("Cursor out of bounds");
-                        if exploded {
-                            return Ok(Some(false));
+                    if self.assisted_opening && self.get_unchecked(crow, ccol).is_open() {
+                        if let Some(outcome) = self.chord(crow, ccol) {
+                            return Ok(outcome);
                         }
                     } else {
-                        if self
-                            .uncover_at(crow, ccol)
-                            .expect("Cursor out of bounds")
-                        {
-                            return Ok(Some(false));
+                        if self.no_guess && self.is_guess(&[(crow, ccol)]) {
+                            return Ok(Outcome::Guessed);
                         }
+
+                        if self.apply(GameEvent::Open {
+                            row: crow,
+                            col: ccol,
+                        }) {
+                            return Ok(Outcome::Lost);
+                        }
+                    }
+                }
+                Key::Char('c' | 'C') if first_move => {
+                    self.message = Some(String::from("Can't chord: open a cell first"));
+                }
+                Key::Char('c' | 'C') => {
+                    if let Some(outcome) = self.chord(crow, ccol) {
+                        return Ok(outcome);
                     }
                 }
-                Key::Char('f' | 'F') if !first_move => {
-                    if assisted_flagging {
+                Key::Char('f' | 'F') if first_move => {
+                    self.message = Some(String::from("Can't flag: open a cell first"));
+                }
+                Key::Char('f' | 'F') => {
+                    if self.assisted_flagging {
                         let cell = self.get_unchecked(crow, ccol);
 
                         let non_open_nbors = self
@@ -215,21 +1132,38 @@ impl Mnswpr {
                             .expect("Position out of bounds");
 
                         if cell.is_open() && cell.neighbouring_bomb_count == non_open_nbors {
-                            self.unflag_all_closed_around(crow, ccol);
+                            self.apply(GameEvent::FlagAround {
+                                row: crow,
+                                col: ccol,
+                            });
                         }
                     }
 
-                    self.toggle_flag_at(crow, ccol)
-                        .expect("Cursor position out of bounds");
+                    let cell = self.get_unchecked(crow, ccol);
+                    if matches!(cell.state, State::Closed) && !self.can_flag() {
+                        self.message = Some(String::from("Can't flag: flag limit reached"));
+                    } else {
+                        self.apply(GameEvent::Flag {
+                            row: crow,
+                            col: ccol,
+                        });
+                    }
                 }
                 _ => {}
             }
+            if self.auto_solve && !first_move && !self.is_paused() {
+                self.auto_solve_trivial();
+            }
+            self.report_progress(true);
+            if let Some(coop) = &mut self.coop {
+                coop.share_cursor(self.cursor.row, self.cursor.col);
+            }
             self.print_game_state(stdout, false)?;
             if self.field.closed_empty_cells == 0 {
-                return Ok(Some(true));
+                return Ok(Outcome::Won);
             }
         }
-        Ok(None)
+        Ok(Outcome::Quit)
     }
 }
 
@@ -246,3 +1180,53 @@ impl DerefMut for Mnswpr {
         &mut self.field
     }
 }
+
+/// Returns whether a board of `cols` columns drawn with `palette` fits in the width of the terminal
+fn fits_in_terminal(cols: usize, palette: &Palette) -> bool {
+    termion::terminal_size().map_or(true, |(width, _)| {
+        cols * palette.glyphs.cell_width() <= (width as usize).saturating_sub(2)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::MNSWPR_PALETTE;
+    use crate::save::Save;
+
+    #[test]
+    fn a_saved_game_resumes_where_it_was_left() {
+        let mut played = Mnswpr::new(3, 4, 20, MNSWPR_PALETTE);
+        played.field.set_mines(&[(0, 0), (2, 3)]);
+        played.replay = Replay::new(&played.field);
+        played.replay.seed = Some(42);
+        played.apply(GameEvent::Open { row: 2, col: 0 });
+        played.apply(GameEvent::Flag { row: 0, col: 0 });
+
+        let yaml = serde_yaml::to_string(&Save::new(played.replay.clone(), 20, 12.5)).unwrap();
+        let save: Save = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(save.progress(), (6, 10));
+
+        let mut resumed = Mnswpr::new(9, 9, 20, MNSWPR_PALETTE);
+        resumed.resume(save.replay, Duration::from_secs_f64(save.seconds));
+        assert_eq!((resumed.rows, resumed.cols), (3, 4));
+        assert_eq!((resumed.cursor.row, resumed.cursor.col), (0, 0));
+        assert_eq!(resumed.game_seed, 42);
+        assert!(!resumed.timer.is_running());
+        assert_eq!(resumed.timer.elapsed(), Duration::from_millis(12500));
+        assert_eq!(resumed.replay.events.len(), 2);
+        assert_eq!(resumed.closed_empty_cells, played.closed_empty_cells);
+        assert_eq!(resumed.flag_count, 1);
+        for row in 0..3 {
+            for col in 0..4 {
+                let (a, b) = (
+                    played.get_unchecked(row, col),
+                    resumed.get_unchecked(row, col),
+                );
+                assert_eq!(a.is_open(), b.is_open(), "({row}, {col})");
+                assert_eq!(a.is_flagged(), b.is_flagged(), "({row}, {col})");
+                assert_eq!(a.contains_mine(), b.contains_mine(), "({row}, {col})");
+            }
+        }
+    }
+}
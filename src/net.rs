@@ -0,0 +1,268 @@
+//! Messages between the instances of mnswpr that take part in the same game, over TCP or a Unix socket.
+//!
+//! Every message is a yaml document followed by a line containing only `---`.
+//! Incoming messages are read and outgoing ones written on separate threads, so that the game never blocks on the network
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::thread;
+
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The port used when none is given
+pub const DEFAULT_PORT: u16 = 7171;
+
+const SEPARATOR: &str = "---";
+
+/// How many messages can wait to be sent. Once that many are waiting, the other side is considered stalled
+/// and the connection gets closed, rather than letting it slow down the game
+const SEND_QUEUE: usize = 256;
+
+/// A stream to another instance of mnswpr
+pub enum Stream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            Stream::Tcp(stream) => Stream::Tcp(stream.try_clone()?),
+            Stream::Unix(stream) => Stream::Unix(stream.try_clone()?),
+        })
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            Stream::Unix(stream) => stream.shutdown(Shutdown::Both),
+        }
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_nonblocking(nonblocking),
+            Stream::Unix(stream) => stream.set_nonblocking(nonblocking),
+        }
+    }
+}
+
+impl From<TcpStream> for Stream {
+    fn from(stream: TcpStream) -> Self {
+        Stream::Tcp(stream)
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Waits for other instances of mnswpr to connect, on a TCP port or a Unix socket
+pub enum Listener {
+    Tcp(TcpListener),
+    /// The path of the socket is removed when the listener is dropped
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    /// Listens on `addr`, which is either a port or the path of a Unix socket, see `socket_path`.
+    /// The connections are accepted without blocking, see `accept`
+    pub fn bind(addr: &str) -> Result<Self> {
+        let listener = match (addr.parse::<u16>(), socket_path(addr)) {
+            (Ok(port), _) => Listener::Tcp(listen(port)?),
+            (Err(_), Some(path)) => {
+                // A socket left behind by an instance that did not exit cleanly would prevent binding.
+                // Anything else at that path is left alone
+                match fs::symlink_metadata(path) {
+                    Ok(metadata) if metadata.file_type().is_socket() => {
+                        let _ = fs::remove_file(path);
+                    }
+                    Ok(_) => bail!("Could not listen on {addr}: path exists and is not a socket"),
+                    Err(_) => {}
+                }
+                let listener = UnixListener::bind(path)
+                    .with_context(|| format!("Could not listen on the socket {addr}"))?;
+                Listener::Unix(listener, path.to_path_buf())
+            }
+            (Err(_), None) => bail!(
+                "\"{addr}\" is neither a port nor the path of a Unix socket, which must contain a `/`, e.g. \"./{addr}\""
+            ),
+        };
+        match &listener {
+            Listener::Tcp(listener) => listener.set_nonblocking(true)?,
+            Listener::Unix(listener, _) => listener.set_nonblocking(true)?,
+        }
+        Ok(listener)
+    }
+
+    /// Returns the next instance that connected, if there is one
+    pub fn accept(&self) -> Result<Option<Stream>> {
+        let stream = match self {
+            Listener::Tcp(listener) => listener.accept().map(|(stream, _)| Stream::Tcp(stream)),
+            Listener::Unix(listener, _) => {
+                listener.accept().map(|(stream, _)| Stream::Unix(stream))
+            }
+        };
+        match stream {
+            Ok(stream) => {
+                stream.set_nonblocking(false)?;
+                Ok(Some(stream))
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Listener::Unix(_, path) = self {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// A connection to another instance of mnswpr, receiving messages of type `T`
+pub struct Connection<T> {
+    /// Only used to close the connection, the threads have their own handles to the stream
+    stream: Stream,
+    outgoing: SyncSender<String>,
+    incoming: Receiver<T>,
+}
+
+impl<T: DeserializeOwned + Send + 'static> Connection<T> {
+    pub fn new(stream: impl Into<Stream>) -> Result<Self> {
+        let stream = stream.into();
+        let reader = BufReader::new(
+            stream
+                .try_clone()
+                .context("Could not read from the connection.")?,
+        );
+        let mut writer = stream
+            .try_clone()
+            .context("Could not write to the connection.")?;
+
+        let (outgoing, documents) = mpsc::sync_channel::<String>(SEND_QUEUE);
+        thread::spawn(move || {
+            for document in documents {
+                let sent = writeln!(writer, "{document}{SEPARATOR}").and_then(|_| writer.flush());
+                if sent.is_err() {
+                    break;
+                }
+            }
+            // The connection is closed once it is dropped and the messages are sent, or once a message could not be.
+            // This also ends the reader, so that a lost connection is noticed when receiving
+            let _ = writer.shutdown();
+        });
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut document = String::new();
+            for line in reader.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if line != SEPARATOR {
+                    document.push_str(&line);
+                    document.push('\n');
+                    continue;
+                }
+
+                // A message that can't be understood ends the connection, like a closed one does
+                let Ok(message) = serde_yaml::from_str(&document) else {
+                    break;
+                };
+                if tx.send(message).is_err() {
+                    break;
+                }
+                document.clear();
+            }
+        });
+
+        Ok(Self {
+            stream,
+            outgoing,
+            incoming: rx,
+        })
+    }
+
+    /// Queues `message` to be sent, without waiting for it to be written.
+    /// Returns an error if the connection was lost, or if the other side stopped reading, which closes the connection
+    pub fn send(&mut self, message: &impl Serialize) -> Result<()> {
+        let document = serde_yaml::to_string(message)?;
+        match self.outgoing.try_send(document) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                let _ = self.stream.shutdown();
+                anyhow::bail!("The other side stopped reading the messages")
+            }
+            Err(TrySendError::Disconnected(_)) => anyhow::bail!("Could not send a message."),
+        }
+    }
+
+    /// Returns the next message that was received, if there is one.
+    /// Returns an error once the other side closed the connection and every message was read
+    pub fn try_receive(&self) -> Result<Option<T>> {
+        match self.incoming.try_recv() {
+            Ok(message) => Ok(Some(message)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => anyhow::bail!("The connection was closed"),
+        }
+    }
+
+    /// Waits for the next message
+    pub fn receive(&self) -> Result<T> {
+        self.incoming.recv().context("The connection was closed")
+    }
+}
+
+/// Listens on `port` on every interface, so that players on the same network can connect
+pub fn listen(port: u16) -> Result<TcpListener> {
+    TcpListener::bind(("0.0.0.0", port)).with_context(|| format!("Could not listen on port {port}"))
+}
+
+/// Returns the path of the Unix socket `addr` refers to, if it does. Paths always contain a `/`,
+/// so that they cannot be mistaken for a host name: a socket in the current directory is written `./mnswpr.sock`
+fn socket_path(addr: &str) -> Option<&Path> {
+    addr.contains('/').then(|| Path::new(addr))
+}
+
+/// Connects to `addr`, which is a host name or an ip address, followed by `:<port>` unless it is `DEFAULT_PORT`,
+/// or the path of a Unix socket, see `socket_path`
+pub fn connect(addr: &str) -> Result<Stream> {
+    let stream = match addr.parse::<IpAddr>() {
+        _ if socket_path(addr).is_some() => UnixStream::connect(addr).map(Stream::Unix),
+        Ok(ip) => TcpStream::connect(SocketAddr::new(ip, DEFAULT_PORT)).map(Stream::Tcp),
+        Err(_) if !addr.contains(':') => TcpStream::connect((addr, DEFAULT_PORT)).map(Stream::Tcp),
+        Err(_) => TcpStream::connect(addr).map(Stream::Tcp),
+    };
+    stream.with_context(|| format!("Could not connect to {addr}"))
+}
@@ -0,0 +1,181 @@
+use std::fmt::Display;
+use std::io::Write;
+
+use crate::replay::{Event, Replay};
+use crate::solver::Solver;
+
+/// How an uncover looked from the point of view of the solver, given what was visible at that time
+pub enum Verdict {
+    /// Every cell that got uncovered could be proven safe
+    Safe,
+    /// Nothing could be proven safe, so a guess was unavoidable
+    ForcedGuess {
+        /// The estimated probability that the move would hit a mine
+        probability: f64,
+        /// The guess the solver would have made, together with its probability of hitting a mine
+        best: Option<((usize, usize), f64)>,
+    },
+    /// The move was not provably safe, but another cell was
+    Mistake { safe: (usize, usize) },
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::Safe => write!(f, "safe"),
+            Verdict::ForcedGuess { probability, .. } => {
+                write!(
+                    f,
+                    "forced guess ({:.0}% chance of a mine)",
+                    probability * 100.0
+                )
+            }
+            Verdict::Mistake { safe: (row, col) } => write!(
+                f,
+                "avoidable mistake (row {}, col {} was provably safe)",
+                row + 1,
+                col + 1
+            ),
+        }
+    }
+}
+
+pub struct MoveAnalysis {
+    pub event: Event,
+    pub verdict: Verdict,
+    /// Whether this move uncovered a mine
+    pub exploded: bool,
+}
+
+impl MoveAnalysis {
+    /// Describes what the solver would have done instead of this move
+    pub fn suggestion(&self) -> Option<String> {
+        match self.verdict {
+            Verdict::Safe => None,
+            Verdict::Mistake { safe: (row, col) } => Some(format!(
+                "The solver would have opened row {}, col {}, which was provably safe",
+                row + 1,
+                col + 1
+            )),
+            Verdict::ForcedGuess {
+                best: Some(((row, col), probability)),
+                ..
+            } => Some(format!(
+                "The solver would have guessed row {}, col {} ({:.0}% chance of a mine)",
+                row + 1,
+                col + 1,
+                probability * 100.0
+            )),
+            Verdict::ForcedGuess { best: None, .. } => None,
+        }
+    }
+}
+
+/// Replays the game and judges every move that uncovered cells
+pub fn analyze(replay: &Replay) -> Vec<MoveAnalysis> {
+    let mut field = replay.initial_field();
+    let mut analysis = Vec::new();
+
+    for event in &replay.events {
+        if !event.is_uncover() {
+            event.apply(&mut field);
+            continue;
+        }
+
+        let (row, col) = event.position().expect("uncovers have a position");
+        let (uncovered, keep_questioned) = match *event {
+            Event::Chord {
+                keep_questioned, ..
+            } => (field.neighbours(row, col), keep_questioned),
+            _ => (vec![(row, col)], false),
+        };
+        let uncovered: Vec<(usize, usize)> = uncovered
+            .into_iter()
+            .filter(|&(r, c)| {
+                field.get(r, c).is_some_and(|cell| {
+                    cell.is_closed() && !(keep_questioned && cell.is_questioned())
+                })
+            })
+            .collect();
+
+        let mut solver = Solver::new(&field);
+        solver.solve();
+
+        // The first click of a game is always safe, even though the solver cannot know it
+        let first_click = field.closed_empty_cells + field.mine_count == field.rows * field.cols;
+
+        let verdict = if first_click || uncovered.iter().all(|&(r, c)| solver.is_safe(r, c)) {
+            Verdict::Safe
+        } else if let Some(&safe) = solver.safe_cells().first() {
+            Verdict::Mistake { safe }
+        } else {
+            Verdict::ForcedGuess {
+                probability: uncovered
+                    .iter()
+                    .map(|&(r, c)| solver.mine_probability(r, c))
+                    .fold(0.0, f64::max),
+                best: solver
+                    .best_guess()
+                    .map(|(r, c)| ((r, c), solver.mine_probability(r, c))),
+            }
+        };
+
+        let exploded = event.apply(&mut field);
+        analysis.push(MoveAnalysis {
+            event: *event,
+            verdict,
+            exploded,
+        });
+    }
+    analysis
+}
+
+/// Writes the analysis of the game, keeping at most `max_lines` lines.
+/// The losing move and a summary are always written, the other moves are written starting from the most recent one
+pub fn print_analysis(
+    f: &mut impl Write,
+    analysis: &[MoveAnalysis],
+    max_lines: usize,
+) -> anyhow::Result<()> {
+    let count = |pred: fn(&Verdict) -> bool| analysis.iter().filter(|a| pred(&a.verdict)).count();
+    write!(
+        f,
+        "Safe moves: {}    Forced guesses: {}    Mistakes: {}\r\n",
+        count(|v| matches!(v, Verdict::Safe)),
+        count(|v| matches!(v, Verdict::ForcedGuess { .. })),
+        count(|v| matches!(v, Verdict::Mistake { .. })),
+    )?;
+
+    let Some((losing, others)) = analysis.split_last() else {
+        return Ok(());
+    };
+
+    let shown = max_lines.saturating_sub(4).min(others.len());
+    for (i, a) in others.iter().enumerate().skip(others.len() - shown) {
+        write!(f, "{:>4}. {}: {}\r\n", i + 1, describe(&a.event), a.verdict)?;
+    }
+
+    write!(
+        f,
+        "{}{:>4}. {}: {}{}{}\r\n",
+        termion::style::Bold,
+        analysis.len(),
+        describe(&losing.event),
+        losing.verdict,
+        if losing.exploded { ", hit a mine" } else { "" },
+        termion::style::Reset
+    )?;
+    if let Some(suggestion) = losing.suggestion() {
+        write!(f, "      {suggestion}\r\n")?;
+    }
+    Ok(())
+}
+
+fn describe(event: &Event) -> String {
+    let (row, col) = event.position().expect("only uncovers are described");
+    let action = match event {
+        Event::Chord { .. } => "chord",
+        _ => "open",
+    };
+    format!("{action} row {}, col {}", row + 1, col + 1)
+}
@@ -0,0 +1,275 @@
+use std::fs;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::field::Field;
+use crate::solver::{solve_logically, Technique};
+
+/// A partially revealed field that can be cleared without ever guessing
+pub struct Puzzle {
+    pub field: Field,
+    /// The hardest technique needed to clear the field, or None if the field is already cleared
+    pub technique: Option<Technique>,
+}
+
+impl Puzzle {
+    /// Parses a puzzle from its textual representation.
+    ///
+    /// Each non-empty line that does not start with `#` is a row of the field, and each char is a cell:
+    /// - `x` is a closed cell containing a mine
+    /// - `-` is a closed empty cell
+    /// - `o` is an open cell
+    /// - a digit is an open cell which must have exactly that amount of neighbouring mines
+    ///
+    /// Returns an error if the puzzle is malformed or if it cannot be solved without guessing
+    pub fn parse(s: &str) -> Result<Self> {
+        let lines: Vec<&str> = s
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .collect();
+
+        let rows = lines.len();
+        if rows == 0 {
+            bail!("The puzzle is empty");
+        }
+        let cols = lines[0].chars().count();
+
+        let mut mines = Vec::new();
+        let mut open = Vec::new();
+        let mut numbers = Vec::new();
+
+        for (row, line) in lines.iter().enumerate() {
+            if line.chars().count() != cols {
+                bail!(
+                    "Row {} has {} cells, expected {cols}",
+                    row + 1,
+                    line.chars().count()
+                );
+            }
+
+            for (col, c) in line.chars().enumerate() {
+                match c {
+                    'x' | 'X' => mines.push((row, col)),
+                    '-' => {}
+                    'o' | 'O' => open.push((row, col)),
+                    '0'..='8' => {
+                        open.push((row, col));
+                        numbers.push((row, col, c.to_digit(10).unwrap() as usize));
+                    }
+                    c => bail!(
+                        "Unexpected char '{c}' at row {}, column {}",
+                        row + 1,
+                        col + 1
+                    ),
+                }
+            }
+        }
+
+        let mut field = Field::new(rows, cols);
+        field.set_mines(&mines);
+
+        for (row, col) in open {
+            field.reveal_at(row, col).ok_or_else(|| {
+                anyhow!(
+                    "The cell at row {}, column {} is open but contains a mine",
+                    row + 1,
+                    col + 1
+                )
+            })?;
+        }
+
+        for (row, col, n) in numbers {
+            let actual = field.get_unchecked(row, col).neighbouring_bomb_count;
+            if actual != n {
+                bail!(
+                    "The cell at row {}, column {} says {n} but it has {actual} neighbouring mines",
+                    row + 1,
+                    col + 1
+                );
+            }
+        }
+
+        Self::from_field(field)
+    }
+
+    /// Reads and parses the puzzle stored in the file at `path`
+    pub fn from_file(path: &str) -> Result<Self> {
+        let data = fs::read_to_string(path).context("Could not read the puzzle file.")?;
+        Self::parse(&data).with_context(|| format!("Invalid puzzle at \"{path}\""))
+    }
+
+    /// Generates a random field with a safe opening around its center which can be cleared without guessing
+    pub fn generate(rows: usize, cols: usize, mine_percentage: u8) -> Result<Self> {
+        const MAX_ATTEMPTS: usize = 1000;
+
+        let mut field = Field::new(rows, cols);
+        let (row, col) = (field.rows / 2, field.cols / 2);
+
+        for _ in 0..MAX_ATTEMPTS {
+            field.randomize(mine_percentage, row, col);
+            field.uncover_at(row, col);
+
+            if let Ok(puzzle) = Self::from_field(field.clone()) {
+                return Ok(puzzle);
+            }
+        }
+        bail!("Could not generate a puzzle without guesses in {MAX_ATTEMPTS} attempts, try lowering the mine percentage")
+    }
+
+    fn from_field(field: Field) -> Result<Self> {
+        let mut solved = field.clone();
+        let technique = solve_logically(&mut solved);
+
+        if solved.closed_empty_cells != 0 {
+            bail!("The puzzle cannot be solved without guessing");
+        }
+        Ok(Self { field, technique })
+    }
+}
+
+/// Built-in puzzles, in the format accepted by `Puzzle::parse`
+const BUILTIN_PUZZLES: &[&str] = &[
+    "
+    01---xx-
+    01x1123-
+    0111001x
+    00000011
+    00111000
+    001x1000
+    ",
+    "
+    --100000
+    -x100111
+    -21001x-
+    x10001--
+    110001--
+    000001xx
+    ",
+    "
+    x--x1001x
+    ---11012-
+    ---1001x-
+    -xx1012--
+    ---212x--
+    ----x----
+    --------x
+    ",
+    "
+    ---101-10
+    --x101x10
+    x-2101110
+    -x1000000
+    --2111111
+    --x--x--x
+    --x------
+    ",
+    "
+    0001-x-x-
+    0001x----
+    0001111x-
+    0000001-x
+    0011101--
+    001x112x-
+    001---x--
+    ",
+    "
+    000000
+    000000
+    111000
+    -x2000
+    -x2011
+    --101x
+    ",
+    "
+    ------
+    -x--xx
+    --214x
+    -x102x
+    --2011
+    -x1000
+    ",
+    "
+    ------
+    x-xxx-
+    -x433x
+    -x2011
+    x21000
+    -10000
+    ",
+];
+
+/// Returns the built-in puzzles that need `technique` to be solved.
+/// If `technique` is None, returns every built-in puzzle sorted by the technique needed to solve it
+pub fn builtin_pack(technique: Option<Technique>) -> Vec<Puzzle> {
+    let mut puzzles: Vec<Puzzle> = BUILTIN_PUZZLES
+        .iter()
+        .map(|s| Puzzle::parse(s).expect("Built-in puzzles are valid"))
+        .filter(|p| technique.is_none() || p.technique == technique)
+        .collect();
+    puzzles.sort_by_key(|p| p.technique);
+    puzzles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_mines_open_cells_and_numbers() {
+        let puzzle = Puzzle::parse(
+            "
+            # The numbers are checked against the mines
+            x1-0
+            ",
+        )
+        .unwrap();
+
+        assert_eq!((puzzle.field.rows, puzzle.field.cols), (1, 4));
+        assert_eq!(puzzle.field.mine_count, 1);
+        assert!(puzzle.field.get_unchecked(0, 0).contains_mine());
+        assert!(puzzle.field.get_unchecked(0, 1).is_open());
+        assert!(!puzzle.field.get_unchecked(0, 2).is_open());
+        assert_eq!(puzzle.technique, Some(Technique::Trivial));
+    }
+
+    #[test]
+    fn parse_rejects_malformed_puzzles() {
+        let error = |s: &str| Puzzle::parse(s).err().unwrap().to_string();
+
+        assert_eq!(error("\n# only a comment\n"), "The puzzle is empty");
+        assert_eq!(error("x1-0\nx1-"), "Row 2 has 3 cells, expected 4");
+        assert_eq!(error("x1?0"), "Unexpected char '?' at row 1, column 3");
+        assert_eq!(
+            error("x2-0"),
+            "The cell at row 1, column 2 says 2 but it has 1 neighbouring mines"
+        );
+    }
+
+    #[test]
+    fn parse_rejects_puzzles_that_need_a_guess() {
+        assert_eq!(
+            Puzzle::parse("x-\n-1").err().unwrap().to_string(),
+            "The puzzle cannot be solved without guessing"
+        );
+    }
+
+    #[test]
+    fn every_builtin_puzzle_is_solved_by_its_technique() {
+        for technique in [
+            Technique::Trivial,
+            Technique::Subset,
+            Technique::GlobalCount,
+        ] {
+            let pack = builtin_pack(Some(technique));
+            assert!(!pack.is_empty(), "no built-in {technique} puzzle");
+
+            for puzzle in pack {
+                let mut field = puzzle.field.clone();
+                assert_eq!(solve_logically(&mut field), Some(technique));
+                assert_eq!(field.closed_empty_cells, 0);
+            }
+        }
+        assert_eq!(builtin_pack(None).len(), BUILTIN_PUZZLES.len());
+    }
+}
@@ -0,0 +1,75 @@
+use std::io::Write;
+
+use termion::cursor::Goto;
+
+use crate::colors::{BG_RESET, FG_RESET};
+
+/// Draws frames made of a header line followed by a grid of cells.
+/// Remembers the last frame it drew, and only writes the parts of the next frame that changed.
+pub struct Renderer {
+    header: Option<String>,
+    cells: Vec<String>,
+    /// The number of terminal columns taken by each cell
+    pub cell_width: usize,
+}
+
+impl Renderer {
+    pub fn new(cell_width: usize) -> Self {
+        Self {
+            header: None,
+            cells: Vec::new(),
+            cell_width,
+        }
+    }
+
+    /// Forgets the last frame, so that the next one gets drawn entirely.
+    /// Must be called whenever something else draws over the frame, e.g. when the screen gets cleared
+    pub fn invalidate(&mut self) {
+        self.header = None;
+        self.cells.clear();
+    }
+
+    /// Draws `header` on the first line of the terminal and `cells`, a grid with `cols` columns, right below it.
+    /// Leaves the terminal cursor at the start of the line after the grid.
+    /// May return an error if it was not able to write in `f`
+    pub fn draw(
+        &mut self,
+        f: &mut impl Write,
+        header: String,
+        cells: Vec<String>,
+        cols: usize,
+    ) -> anyhow::Result<()> {
+        let mut out = String::new();
+
+        if self.header.as_ref() != Some(&header) {
+            out.push_str(&format!(
+                "{}{header}{}",
+                Goto(1, 1),
+                termion::clear::UntilNewline
+            ));
+            self.header = Some(header);
+        }
+
+        if self.cells.len() != cells.len() {
+            self.cells = vec![String::new(); cells.len()];
+        }
+
+        for (idx, cell) in cells.into_iter().enumerate() {
+            if self.cells[idx] == cell {
+                continue;
+            }
+
+            let row = idx / cols;
+            let col = idx % cols;
+            out.push_str(&format!(
+                "{}{cell}",
+                Goto((col * self.cell_width + 1) as u16, (row + 2) as u16)
+            ));
+            self.cells[idx] = cell;
+        }
+
+        let rows = self.cells.len() / cols.max(1);
+        write!(f, "{out}{BG_RESET}{FG_RESET}{}", Goto(1, (rows + 2) as u16))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::field::Field;
+use crate::solver::Rating;
+
+/// Something the player did to the field
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Event {
+    /// Uncovered the cell at (row, col)
+    Open { row: usize, col: usize },
+    /// Uncovered the closed cells around the cell at (row, col), except the questioned ones if `keep_questioned`
+    Chord {
+        row: usize,
+        col: usize,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        keep_questioned: bool,
+    },
+    /// Moved the cell at (row, col) to the next state of the flag cycle: closed, flagged, questioned
+    Flag { row: usize, col: usize },
+    /// Flagged every closed cell around the cell at (row, col)
+    FlagAround { row: usize, col: usize },
+    /// Paused the game, which hides the board and stops the timer
+    Pause,
+    /// Went back to the game after a pause that lasted `seconds`
+    Unpause { seconds: f64 },
+}
+
+impl Event {
+    /// Applies the event to `field`. Returns whether a mine was uncovered
+    pub fn apply(&self, field: &mut Field) -> bool {
+        match *self {
+            Event::Open { row, col } => field.uncover_at(row, col).unwrap_or(false),
+            Event::Chord {
+                row,
+                col,
+                keep_questioned,
+            } => field
+                .uncover_around_cell_at(row, col, keep_questioned)
+                .unwrap_or(false),
+            Event::Flag { row, col } => {
+                field.toggle_flag_at(row, col);
+                false
+            }
+            Event::FlagAround { row, col } => {
+                field.unflag_all_closed_around(row, col);
+                false
+            }
+            Event::Pause | Event::Unpause { .. } => false,
+        }
+    }
+
+    /// Returns whether the event uncovers cells
+    pub fn is_uncover(&self) -> bool {
+        matches!(self, Event::Open { .. } | Event::Chord { .. })
+    }
+
+    /// Returns the position (row, col) the event was performed on, if it was performed on a cell
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match *self {
+            Event::Open { row, col }
+            | Event::Chord { row, col, .. }
+            | Event::Flag { row, col }
+            | Event::FlagAround { row, col } => Some((row, col)),
+            Event::Pause | Event::Unpause { .. } => None,
+        }
+    }
+}
+
+/// Everything needed to play a game again: the starting field and what the player did to it
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Replay {
+    pub rows: usize,
+    pub cols: usize,
+    pub mines: Vec<(usize, usize)>,
+    /// The cells that were already open when the game started
+    pub open: Vec<(usize, usize)>,
+    /// Whether more flags than mines could be placed
+    #[serde(default)]
+    pub unlimited_flags: bool,
+    pub rating: Option<Rating>,
+    /// The seed the field was generated from, if it was randomized
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub events: Vec<Event>,
+}
+
+impl Replay {
+    /// Starts a replay from the current content of `field`
+    pub fn new(field: &Field) -> Self {
+        let mut replay = Self {
+            rows: field.rows,
+            cols: field.cols,
+            unlimited_flags: field.unlimited_flags,
+            ..Default::default()
+        };
+
+        for row in 0..field.rows {
+            for col in 0..field.cols {
+                let cell = field.get_unchecked(row, col);
+                if cell.contains_mine() {
+                    replay.mines.push((row, col));
+                } else if cell.is_open() {
+                    replay.open.push((row, col));
+                }
+            }
+        }
+        replay
+    }
+
+    /// Generates a board from the random numbers of `rng`, with the area around its center open,
+    /// so that an `rng` seeded the same way always gives the same game whoever plays it
+    pub fn seeded(
+        rng: &mut impl Rng,
+        rows: usize,
+        cols: usize,
+        mine_percentage: u8,
+        unlimited_flags: bool,
+    ) -> Self {
+        let (row, col) = (rows / 2, cols / 2);
+
+        let mut field = Field::new(rows, cols);
+        field.unlimited_flags = unlimited_flags;
+        field.randomize_with(rng, mine_percentage, row, col);
+        field.uncover_at(row, col);
+
+        Self::new(&field)
+    }
+
+    /// Returns how many times the game was paused, and for how many seconds in total
+    pub fn pauses(&self) -> (usize, f64) {
+        self.events
+            .iter()
+            .fold((0, 0.0), |(count, total), event| match event {
+                Event::Unpause { seconds } => (count + 1, total + seconds),
+                _ => (count, total),
+            })
+    }
+
+    /// Builds the field as it was at the start of the game
+    pub fn initial_field(&self) -> Field {
+        let mut field = Field::new(self.rows, self.cols);
+        field.unlimited_flags = self.unlimited_flags;
+        field.set_mines(&self.mines);
+        for &(row, col) in &self.open {
+            field.reveal_at(row, col);
+        }
+        field
+    }
+}
@@ -0,0 +1,90 @@
+//! Games that were quit before they ended, kept in the user's data directory so that they can be resumed from the menu
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::replay::Replay;
+use crate::stats;
+
+/// A game that can be resumed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Save {
+    /// When the game was saved, in seconds since the unix epoch
+    pub timestamp: u64,
+    /// The mine percentage of the next games, if the player keeps playing after the resumed one
+    pub mine_percentage: u8,
+    /// The play time of the game when it was saved, pauses excluded
+    #[serde(default)]
+    pub seconds: f64,
+    /// The starting field and every move made so far
+    pub replay: Replay,
+}
+
+impl Save {
+    pub fn new(replay: Replay, mine_percentage: u8, seconds: f64) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            mine_percentage,
+            seconds,
+            replay,
+        }
+    }
+
+    /// Returns the number of cells that were open when the game was saved, out of the empty ones
+    pub fn progress(&self) -> (usize, usize) {
+        let mut field = self.replay.initial_field();
+        for event in &self.replay.events {
+            event.apply(&mut field);
+        }
+        let empty = field.rows * field.cols - field.mine_count;
+        (empty - field.closed_empty_cells, empty)
+    }
+
+    /// Writes the save to a new file in the saves directory
+    pub fn store(&self) -> Result<()> {
+        let Some(dir) = dir() else {
+            return Ok(());
+        };
+        fs::create_dir_all(&dir).context("Could not create the saves directory.")?;
+
+        let mut path = dir.join(format!("{}.yaml", self.timestamp));
+        let mut copy = 1;
+        while path.exists() {
+            path = dir.join(format!("{}-{copy}.yaml", self.timestamp));
+            copy += 1;
+        }
+        fs::write(&path, serde_yaml::to_string(self)?).context("Could not write the saved game.")
+    }
+}
+
+/// Returns the directory of the saved games: `$XDG_DATA_HOME/mnswpr/saves`
+fn dir() -> Option<PathBuf> {
+    Some(stats::data_dir()?.join("saves"))
+}
+
+/// Returns the saved games with their paths, the most recent first. Files that cannot be read are skipped
+pub fn list() -> Vec<(PathBuf, Save)> {
+    let Some(entries) = dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut saves: Vec<(PathBuf, Save)> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let save = serde_yaml::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+            Some((path, save))
+        })
+        .collect();
+    saves.sort_by_key(|(_, save): &(PathBuf, Save)| std::cmp::Reverse(save.timestamp));
+    saves
+}
+
+/// Deletes the saved game at `path`, once it has been resumed and played
+pub fn remove(path: &Path) -> Result<()> {
+    fs::remove_file(path).context("Could not delete the saved game.")
+}
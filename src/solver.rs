@@ -0,0 +1,653 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::field::Field;
+
+/// The deduction techniques known by the solver, ordered from the easiest to the hardest
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Technique {
+    /// A single number already tells everything about its closed neighbours
+    Trivial,
+    /// The closed neighbours of a number are a subset of the closed neighbours of another number
+    Subset,
+    /// The amount of mines left in the whole field is needed to conclude something
+    GlobalCount,
+}
+
+impl Display for Technique {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Technique::Trivial => "trivial",
+            Technique::Subset => "subset",
+            Technique::GlobalCount => "global-count",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Technique {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trivial" => Ok(Technique::Trivial),
+            "subset" => Ok(Technique::Subset),
+            "global-count" => Ok(Technique::GlobalCount),
+            v => Err(format!(
+                "Expected one of \"trivial\", \"subset\", \"global-count\". Got \"{v}\""
+            )),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Knowledge {
+    Unknown,
+    Safe,
+    Mine,
+}
+
+/// A number on the board together with the closed cells around it that are still unknown
+struct Constraint {
+    cells: Vec<usize>,
+    mines: usize,
+}
+
+/// Deduces which closed cells of a field are safe and which contain a mine, using only
+/// what the player can see: the open cells and the total amount of mines.
+/// Flags are ignored, since they might have been placed wrongly.
+pub struct Solver<'a> {
+    field: &'a Field,
+    knowledge: Vec<Knowledge>,
+    /// The hardest technique that was needed to make some progress, if any progress was made
+    pub hardest: Option<Technique>,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(field: &'a Field) -> Self {
+        let knowledge = (0..field.rows * field.cols)
+            .map(|idx| {
+                if field
+                    .get_unchecked(idx / field.cols, idx % field.cols)
+                    .is_open()
+                {
+                    Knowledge::Safe
+                } else {
+                    Knowledge::Unknown
+                }
+            })
+            .collect();
+
+        Self {
+            field,
+            knowledge,
+            hardest: None,
+        }
+    }
+
+    /// Applies the deduction techniques, always preferring the easiest one, until nothing else can be deduced
+    pub fn solve(&mut self) {
+        loop {
+            let technique = if self.apply_trivial() {
+                Technique::Trivial
+            } else if self.apply_subset() {
+                Technique::Subset
+            } else if self.apply_global_count() {
+                Technique::GlobalCount
+            } else {
+                return;
+            };
+            self.hardest = self.hardest.max(Some(technique));
+        }
+    }
+
+    /// Applies only the trivial technique, until nothing else can be deduced with it
+    pub fn solve_trivial(&mut self) {
+        while self.apply_trivial() {
+            self.hardest = Some(Technique::Trivial);
+        }
+    }
+
+    /// Returns whether the cell at position (row, col) is known not to contain a mine
+    pub fn is_safe(&self, row: usize, col: usize) -> bool {
+        self.knowledge[self.idx(row, col)] == Knowledge::Safe
+    }
+
+    /// Returns the positions of the closed (or flagged) cells that are known to be safe
+    pub fn safe_cells(&self) -> Vec<(usize, usize)> {
+        self.positions_where(|idx, s| {
+            s.knowledge[idx] == Knowledge::Safe
+                && !s
+                    .field
+                    .get_unchecked(idx / s.field.cols, idx % s.field.cols)
+                    .is_open()
+        })
+    }
+
+    /// Returns the positions of the cells that are known to contain a mine
+    pub fn mine_cells(&self) -> Vec<(usize, usize)> {
+        self.positions_where(|idx, s| s.knowledge[idx] == Knowledge::Mine)
+    }
+
+    /// Estimates the probability that the cell at position (row, col) contains a mine
+    pub fn mine_probability(&self, row: usize, col: usize) -> f64 {
+        let idx = self.idx(row, col);
+        match self.knowledge[idx] {
+            Knowledge::Safe => 0.0,
+            Knowledge::Mine => 1.0,
+            Knowledge::Unknown => self.unknown_probabilities()[&idx],
+        }
+    }
+
+    /// Returns the unknown cell that is least likely to contain a mine among the ones accepted by `pred`
+    pub fn best_guess_where(&self, pred: impl Fn(usize, usize) -> bool) -> Option<(usize, usize)> {
+        self.unknown_probabilities()
+            .into_iter()
+            .map(|(idx, p)| ((idx / self.field.cols, idx % self.field.cols), p))
+            .filter(|&((row, col), _)| pred(row, col))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(position, _)| position)
+    }
+
+    /// Returns the unknown cell that is least likely to contain a mine, if there is any unknown cell
+    pub fn best_guess(&self) -> Option<(usize, usize)> {
+        self.best_guess_where(|_, _| true)
+    }
+
+    /// Estimates the probability that each unknown cell contains a mine.
+    /// Uses the most pessimistic of the numbers around the cell, or the density of the mines
+    /// left in the field if the cell is not next to any number
+    fn unknown_probabilities(&self) -> HashMap<usize, f64> {
+        let unknown: Vec<usize> = (0..self.knowledge.len())
+            .filter(|&idx| self.knowledge[idx] == Knowledge::Unknown)
+            .collect();
+        let density = self.mines_left() as f64 / unknown.len().max(1) as f64;
+
+        let mut local: HashMap<usize, f64> = HashMap::new();
+        for constraint in self.constraints() {
+            let p = constraint.mines as f64 / constraint.cells.len() as f64;
+            for idx in constraint.cells {
+                let entry = local.entry(idx).or_insert(p);
+                *entry = entry.max(p);
+            }
+        }
+
+        unknown
+            .into_iter()
+            .map(|idx| (idx, local.get(&idx).copied().unwrap_or(density)))
+            .collect()
+    }
+
+    fn idx(&self, row: usize, col: usize) -> usize {
+        row * self.field.cols + col
+    }
+
+    fn positions_where(&self, pred: impl Fn(usize, &Self) -> bool) -> Vec<(usize, usize)> {
+        (0..self.knowledge.len())
+            .filter(|&idx| pred(idx, self))
+            .map(|idx| (idx / self.field.cols, idx % self.field.cols))
+            .collect()
+    }
+
+    fn mines_left(&self) -> usize {
+        let known_mines = self
+            .knowledge
+            .iter()
+            .filter(|k| **k == Knowledge::Mine)
+            .count();
+        self.field.mine_count.saturating_sub(known_mines)
+    }
+
+    /// Builds a constraint for every open number that still has unknown neighbours
+    fn constraints(&self) -> Vec<Constraint> {
+        let mut constraints = Vec::new();
+
+        for row in 0..self.field.rows {
+            for col in 0..self.field.cols {
+                let cell = self.field.get_unchecked(row, col);
+                if !cell.is_open() {
+                    continue;
+                }
+
+                let mut cells = Vec::new();
+                let mut known_mines = 0;
+                for (r, c) in self.field.neighbours(row, col) {
+                    match self.knowledge[self.idx(r, c)] {
+                        Knowledge::Unknown => cells.push(self.idx(r, c)),
+                        Knowledge::Mine => known_mines += 1,
+                        Knowledge::Safe => {}
+                    }
+                }
+
+                if !cells.is_empty() {
+                    constraints.push(Constraint {
+                        cells,
+                        mines: cell.neighbouring_bomb_count.saturating_sub(known_mines),
+                    });
+                }
+            }
+        }
+        constraints
+    }
+
+    /// Marks every cell in `cells` as `knowledge`. Returns whether something new was learnt
+    fn mark(&mut self, cells: &[usize], knowledge: Knowledge) -> bool {
+        let mut progress = false;
+        for &idx in cells {
+            if self.knowledge[idx] == Knowledge::Unknown {
+                self.knowledge[idx] = knowledge;
+                progress = true;
+            }
+        }
+        progress
+    }
+
+    fn apply_trivial(&mut self) -> bool {
+        let mut progress = false;
+        for constraint in self.constraints() {
+            if constraint.mines == 0 {
+                progress |= self.mark(&constraint.cells, Knowledge::Safe);
+            } else if constraint.mines == constraint.cells.len() {
+                progress |= self.mark(&constraint.cells, Knowledge::Mine);
+            }
+        }
+        progress
+    }
+
+    fn apply_subset(&mut self) -> bool {
+        let constraints = self.constraints();
+        let mut progress = false;
+
+        // Every superset of a constraint contains its first cell, so only those need to be checked
+        let mut by_cell: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, constraint) in constraints.iter().enumerate() {
+            for &idx in &constraint.cells {
+                by_cell.entry(idx).or_default().push(i);
+            }
+        }
+
+        for small in &constraints {
+            for &i in &by_cell[&small.cells[0]] {
+                let big = &constraints[i];
+                if small.cells.len() >= big.cells.len()
+                    || !small.cells.iter().all(|idx| big.cells.contains(idx))
+                {
+                    continue;
+                }
+
+                let diff: Vec<usize> = big
+                    .cells
+                    .iter()
+                    .filter(|idx| !small.cells.contains(idx))
+                    .copied()
+                    .collect();
+                let diff_mines = big.mines.saturating_sub(small.mines);
+
+                if diff_mines == 0 {
+                    progress |= self.mark(&diff, Knowledge::Safe);
+                } else if diff_mines == diff.len() {
+                    progress |= self.mark(&diff, Knowledge::Mine);
+                }
+            }
+        }
+        progress
+    }
+
+    fn apply_global_count(&mut self) -> bool {
+        let mines_left = self.mines_left();
+        let unknown: Vec<usize> = (0..self.knowledge.len())
+            .filter(|&idx| self.knowledge[idx] == Knowledge::Unknown)
+            .collect();
+
+        if unknown.is_empty() {
+            return false;
+        }
+        if mines_left == 0 {
+            return self.mark(&unknown, Knowledge::Safe);
+        }
+        if mines_left == unknown.len() {
+            return self.mark(&unknown, Knowledge::Mine);
+        }
+
+        // If some disjoint numbers already account for all of the mines left,
+        // every unknown cell outside of them is safe
+        let mut covered = vec![false; self.knowledge.len()];
+        let mut covered_mines = 0;
+        for constraint in self.constraints() {
+            if constraint.cells.iter().any(|&idx| covered[idx]) {
+                continue;
+            }
+            covered_mines += constraint.mines;
+            for idx in constraint.cells {
+                covered[idx] = true;
+            }
+        }
+
+        if covered_mines != mines_left {
+            return false;
+        }
+        let outside: Vec<usize> = unknown.into_iter().filter(|&idx| !covered[idx]).collect();
+        self.mark(&outside, Knowledge::Safe)
+    }
+}
+
+/// Opens every cell that can be proven safe, repeating until the field is cleared or nothing else can be deduced.
+/// Returns the hardest technique that was needed, or None if nothing could be deduced
+pub fn solve_logically(field: &mut Field) -> Option<Technique> {
+    let mut hardest = None;
+
+    while field.closed_empty_cells > 0 {
+        let mut solver = Solver::new(field);
+        solver.solve();
+
+        let safe_cells = solver.safe_cells();
+        if safe_cells.is_empty() {
+            break;
+        }
+        hardest = hardest.max(solver.hardest);
+
+        for (row, col) in safe_cells {
+            field.uncover_at(row, col);
+        }
+    }
+    hardest
+}
+
+/// How a game played by the solver went
+pub struct SolverGame {
+    pub won: bool,
+    /// The number of times the solver had to open a cell that could not be proven safe
+    pub guesses: usize,
+}
+
+/// Plays on `field` until it is cleared or a mine is hit. The first cell must already be open.
+/// Known mines get flagged, and their neighbours are cleared by chording wherever possible.
+/// When nothing can be deduced, opens the cell that is least likely to contain a mine
+pub fn play(field: &mut Field) -> SolverGame {
+    let mut guesses = 0;
+
+    while field.closed_empty_cells > 0 {
+        let mut solver = Solver::new(field);
+        solver.solve();
+        let safe_cells = solver.safe_cells();
+
+        if safe_cells.is_empty() {
+            let Some((row, col)) = solver.best_guess() else {
+                break;
+            };
+            guesses += 1;
+            if field.uncover_at(row, col).expect("Guess out of bounds") {
+                return SolverGame {
+                    won: false,
+                    guesses,
+                };
+            }
+            continue;
+        }
+
+        for (row, col) in solver.mine_cells() {
+            if field.get_unchecked(row, col).is_closed() {
+                field.toggle_flag_at(row, col);
+            }
+        }
+
+        for (row, col) in safe_cells {
+            if !field.get_unchecked(row, col).is_closed() {
+                continue;
+            }
+
+            // Prefer chording on an open neighbour that has all of its mines flagged
+            let chord_from = field.neighbours(row, col).into_iter().find(|&(r, c)| {
+                let cell = field.get_unchecked(r, c);
+                cell.is_open()
+                    && field.get_flagged_nbors_amt(r, c) == Some(cell.neighbouring_bomb_count)
+            });
+            match chord_from {
+                Some((r, c)) => field.uncover_around_cell_at(r, c, false),
+                None => field.uncover_at(row, col),
+            };
+        }
+    }
+
+    SolverGame {
+        won: field.closed_empty_cells == 0,
+        guesses,
+    }
+}
+
+/// How hard a board is, given by the hardest deduction needed to clear it
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Difficulty {
+    Trivial,
+    Subset,
+    GlobalCount,
+    /// The board cannot be cleared without guessing at least once
+    Guess,
+}
+
+impl From<Technique> for Difficulty {
+    fn from(technique: Technique) -> Self {
+        match technique {
+            Technique::Trivial => Difficulty::Trivial,
+            Technique::Subset => Difficulty::Subset,
+            Technique::GlobalCount => Difficulty::GlobalCount,
+        }
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Trivial => Technique::Trivial.fmt(f),
+            Difficulty::Subset => Technique::Subset.fmt(f),
+            Difficulty::GlobalCount => Technique::GlobalCount.fmt(f),
+            Difficulty::Guess => write!(f, "guess"),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "guess" => Ok(Difficulty::Guess),
+            s => s.parse::<Technique>().map(Difficulty::from).map_err(|_| {
+                format!("Expected one of \"trivial\", \"subset\", \"global-count\", \"guess\". Got \"{s}\"")
+            }),
+        }
+    }
+}
+
+/// A range of accepted difficulties, written as `<difficulty>` or `<min>..<max>`
+#[derive(Copy, Clone)]
+pub struct DifficultyBand {
+    pub min: Difficulty,
+    pub max: Difficulty,
+}
+
+impl DifficultyBand {
+    pub fn contains(&self, difficulty: Difficulty) -> bool {
+        self.min <= difficulty && difficulty <= self.max
+    }
+}
+
+impl FromStr for DifficultyBand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (min, max) = match s.split_once("..") {
+            Some((min, max)) => (min.parse()?, max.parse()?),
+            None => (s.parse()?, s.parse()?),
+        };
+        if min > max {
+            return Err(format!("The band \"{s}\" is empty"));
+        }
+        Ok(Self { min, max })
+    }
+}
+
+/// The difficulty of a board, together with the number of guesses needed to clear it
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Rating {
+    pub difficulty: Difficulty,
+    pub forced_guesses: usize,
+}
+
+impl Display for Rating {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {} forced guess{}",
+            self.difficulty,
+            self.forced_guesses,
+            if self.forced_guesses == 1 { "" } else { "es" }
+        )
+    }
+}
+
+/// Rates a freshly randomized `field` as if the first click happened at (row, col).
+/// Whenever the solver is stuck, it counts a forced guess and opens the safe cell that was least likely to contain a mine
+pub fn rate(field: &Field, row: usize, col: usize) -> Rating {
+    let mut field = field.clone();
+    field.uncover_at(row, col);
+
+    let mut hardest = None;
+    let mut forced_guesses = 0;
+
+    loop {
+        hardest = hardest.max(solve_logically(&mut field));
+        if field.closed_empty_cells == 0 {
+            break;
+        }
+
+        let mut solver = Solver::new(&field);
+        solver.solve();
+        let guess = solver.best_guess_where(|r, c| !field.get_unchecked(r, c).contains_mine());
+        let Some((r, c)) = guess else {
+            break;
+        };
+
+        forced_guesses += 1;
+        field.uncover_at(r, c);
+    }
+
+    Rating {
+        difficulty: if forced_guesses > 0 {
+            Difficulty::Guess
+        } else {
+            hardest.map_or(Difficulty::Trivial, Difficulty::from)
+        },
+        forced_guesses,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_number_without_mines_left_makes_its_neighbours_safe() {
+        // x1-0
+        let mut field = Field::new(1, 4);
+        field.set_mines(&[(0, 0)]);
+        field.reveal_at(0, 1);
+        field.reveal_at(0, 3);
+
+        let mut solver = Solver::new(&field);
+        solver.solve();
+        assert_eq!(solver.hardest, Some(Technique::Trivial));
+        assert_eq!(solver.safe_cells(), vec![(0, 2)]);
+        assert!(!solver.is_safe(0, 0));
+    }
+
+    #[test]
+    fn one_two_one_needs_the_subset_technique() {
+        // x-x
+        // 121
+        let mut field = Field::new(2, 3);
+        field.set_mines(&[(0, 0), (0, 2)]);
+        for col in 0..3 {
+            field.reveal_at(1, col);
+        }
+
+        let mut solver = Solver::new(&field);
+        solver.solve();
+        assert_eq!(solver.hardest, Some(Technique::Subset));
+        assert_eq!(solver.safe_cells(), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn cells_out_of_reach_of_numbers_need_the_mine_count() {
+        // 1x--
+        let mut field = Field::new(1, 4);
+        field.set_mines(&[(0, 1)]);
+        field.reveal_at(0, 0);
+
+        let mut solver = Solver::new(&field);
+        solver.solve();
+        assert_eq!(solver.hardest, Some(Technique::GlobalCount));
+        assert_eq!(solver.safe_cells(), vec![(0, 2), (0, 3)]);
+    }
+
+    #[test]
+    fn disjoint_numbers_holding_every_mine_left_clear_the_rest() {
+        // -1---
+        // x1---
+        // 11---
+        // -----
+        let mut field = Field::new(4, 5);
+        field.set_mines(&[(1, 0)]);
+        for (row, col) in [(0, 1), (1, 1), (2, 0), (2, 1)] {
+            field.reveal_at(row, col);
+        }
+
+        let mut solver = Solver::new(&field);
+        assert!(solver.apply_global_count());
+        assert!(solver.is_safe(3, 4));
+        assert!(!solver.is_safe(0, 0));
+    }
+
+    #[test]
+    fn a_coin_flip_cannot_be_solved() {
+        // x-
+        // -1
+        let mut field = Field::new(2, 2);
+        field.set_mines(&[(0, 0)]);
+        field.reveal_at(1, 1);
+
+        let mut solver = Solver::new(&field);
+        solver.solve();
+        assert_eq!(solver.hardest, None);
+        assert!(solver.safe_cells().is_empty());
+    }
+
+    #[test]
+    fn solve_logically_opens_every_safe_cell() {
+        let mut field = Field::new(2, 3);
+        field.set_mines(&[(0, 0), (0, 2)]);
+        for col in 0..3 {
+            field.reveal_at(1, col);
+        }
+
+        assert_eq!(solve_logically(&mut field), Some(Technique::Subset));
+        assert_eq!(field.closed_empty_cells, 0);
+        assert!(field.get_unchecked(0, 1).is_open());
+    }
+
+    #[test]
+    fn techniques_are_parsed_from_their_names() {
+        for technique in [
+            Technique::Trivial,
+            Technique::Subset,
+            Technique::GlobalCount,
+        ] {
+            assert_eq!(technique.to_string().parse(), Ok(technique));
+        }
+        assert!("guess".parse::<Technique>().is_err());
+    }
+}
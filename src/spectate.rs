@@ -0,0 +1,372 @@
+//! Live games that others can watch: a game started with `--spectate <addr>` publishes its board and moves,
+//! and `mnswpr watch <addr>` shows them read-only, with the cursor and the header of the player.
+//!
+//! Spectators get the board as the player sees it whenever it changes, which is also what spectators
+//! that connect in the middle of a game get. The mines and the seed are only sent once the game is over,
+//! so that a spectator cannot tell the player where they are.
+//! Spectators are accepted on a separate thread, so that they get the game as soon as they connect
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::{Content, State};
+use crate::field::Field;
+use crate::net::{self, Connection, Listener};
+use crate::replay::{Event, Replay};
+
+/// How often the thread accepting the spectators checks for new ones
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Message {
+    /// The board as the player sees it, the play time and where the cursor is.
+    /// Sent whenever the board changes, and to the spectators that just connected
+    Board {
+        view: View,
+        seconds: f64,
+        /// Whether the timer is running, which it is once the first move was made
+        running: bool,
+        paused: bool,
+        row: usize,
+        col: usize,
+    },
+    Cursor {
+        row: usize,
+        col: usize,
+    },
+}
+
+/// What the player sees of the board
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct View {
+    pub rows: usize,
+    pub cols: usize,
+    pub mine_count: usize,
+    /// Every row of the board: `-` is a closed cell, `f` a flagged one and `?` a questioned one.
+    /// A digit is an open cell with that many neighbouring mines, and `*` an uncovered mine
+    pub cells: Vec<String>,
+    pub over: bool,
+    /// The mines, which are only given once the game is over
+    pub mines: Vec<(usize, usize)>,
+    /// The seed the board was generated from, only given once the game is over since the mines could be generated again from it
+    pub seed: Option<u64>,
+}
+
+impl View {
+    /// Returns what the player sees of `field`, generated from `seed`.
+    /// The mines are only given if the game is `over`
+    pub fn of(field: &Field, seed: Option<u64>, over: bool) -> Self {
+        let mut mines = Vec::new();
+        let mut cells = Vec::with_capacity(field.rows);
+        for row in 0..field.rows {
+            let mut line = String::with_capacity(field.cols);
+            for col in 0..field.cols {
+                let cell = field.get_unchecked(row, col);
+                if over && cell.contains_mine() {
+                    mines.push((row, col));
+                }
+                line.push(match cell.state {
+                    State::Closed => '-',
+                    State::Flagged => 'f',
+                    State::Questioned => '?',
+                    State::Open if cell.contains_mine() => '*',
+                    State::Open => {
+                        char::from_digit(cell.neighbouring_bomb_count as u32, 10).unwrap_or('-')
+                    }
+                });
+            }
+            cells.push(line);
+        }
+
+        Self {
+            rows: field.rows,
+            cols: field.cols,
+            mine_count: field.mine_count,
+            cells,
+            over,
+            mines,
+            seed: seed.filter(|_| over),
+        }
+    }
+
+    /// Returns a field that looks like the one of the player.
+    /// Until the game is over, every closed cell of it is empty
+    pub fn field(&self) -> Field {
+        let mut field = Field::new(self.rows, self.cols);
+        field.set_mines(&self.mines);
+
+        for (row, line) in self.cells.iter().enumerate().take(field.rows) {
+            for (col, c) in line.chars().enumerate().take(field.cols) {
+                let cell = field.get_mut_unchecked(row, col);
+                match c {
+                    'f' => cell.set_state(State::Flagged),
+                    '?' => cell.set_state(State::Questioned),
+                    '*' => {
+                        cell.content = Content::Mine;
+                        cell.set_state(State::Open);
+                    }
+                    '0'..='8' => {
+                        cell.neighbouring_bomb_count = c.to_digit(10).unwrap_or_default() as usize;
+                        cell.set_state(State::Open);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let closed = self
+            .cells
+            .iter()
+            .flat_map(|line| line.chars())
+            .filter(|c| !matches!(c, '0'..='8' | '*'))
+            .count();
+        field.flag_count = self
+            .cells
+            .iter()
+            .flat_map(|line| line.chars())
+            .filter(|c| *c == 'f')
+            .count();
+        field.mine_count = self.mine_count;
+        field.closed_empty_cells = closed.saturating_sub(self.mine_count);
+        field
+    }
+}
+
+/// The part of a replay that tells games apart
+struct Board {
+    rows: usize,
+    cols: usize,
+    mines: Vec<(usize, usize)>,
+    open: Vec<(usize, usize)>,
+}
+
+impl Board {
+    fn of(replay: &Replay) -> Self {
+        Self {
+            rows: replay.rows,
+            cols: replay.cols,
+            mines: replay.mines.clone(),
+            open: replay.open.clone(),
+        }
+    }
+
+    fn is_board_of(&self, replay: &Replay) -> bool {
+        self.rows == replay.rows
+            && self.cols == replay.cols
+            && self.mines == replay.mines
+            && self.open == replay.open
+    }
+}
+
+/// What the thread accepting the spectators shares with the game
+struct Audience {
+    /// Nothing is read from the spectators, the connection only tells when they leave
+    watchers: Vec<Connection<IgnoredAny>>,
+    /// The game as it was last published, as a `Message::Board`. None until something is published
+    game: Option<Message>,
+}
+
+impl Audience {
+    /// Sends `message` to every spectator, forgetting the ones that left or stopped reading
+    fn broadcast(&mut self, message: &Message) {
+        self.watchers.retain_mut(|watcher| {
+            // The reader only ends once the spectator closed the connection
+            watcher.try_receive().is_ok() && watcher.send(message).is_ok()
+        });
+    }
+}
+
+/// The spectators of the games of this player
+pub struct Spectators {
+    /// The thread accepting the spectators only holds a weak reference, so that it stops once this is dropped
+    _listener: Arc<Listener>,
+    audience: Arc<Mutex<Audience>>,
+    /// The board of the game that was last published
+    board: Option<Board>,
+    /// How many events of that game were published, and whether it was over
+    published: usize,
+    over: bool,
+    cursor: (usize, usize),
+}
+
+impl Spectators {
+    /// Starts accepting spectators on `addr`, a port or the path of a Unix socket
+    pub fn listen(addr: &str) -> Result<Self> {
+        let listener = Arc::new(Listener::bind(addr)?);
+        let audience = Arc::new(Mutex::new(Audience {
+            watchers: Vec::new(),
+            game: None,
+        }));
+
+        let weak_listener = Arc::downgrade(&listener);
+        let shared_audience = Arc::clone(&audience);
+        thread::spawn(move || {
+            while let Some(listener) = weak_listener.upgrade() {
+                while let Ok(Some(stream)) = listener.accept() {
+                    let Ok(mut watcher) = Connection::new(stream) else {
+                        continue;
+                    };
+                    let Ok(mut audience) = shared_audience.lock() else {
+                        return;
+                    };
+                    // Spectators that connect before anything was published get the game with the first publish
+                    let sent = audience
+                        .game
+                        .as_ref()
+                        .map_or(Ok(()), |game| watcher.send(game));
+                    if sent.is_ok() {
+                        audience.watchers.push(watcher);
+                    }
+                }
+                drop(listener);
+                thread::sleep(ACCEPT_INTERVAL);
+            }
+        });
+
+        Ok(Self {
+            _listener: listener,
+            audience,
+            board: None,
+            published: 0,
+            over: false,
+            cursor: (0, 0),
+        })
+    }
+
+    /// Sends what changed since the last call to the spectators: the board of the game recorded in `replay`
+    /// as it is in `field`, which is `over` or not, and the cursor.
+    /// `elapsed` and `running` describe the timer of the game
+    pub fn publish(
+        &mut self,
+        field: &Field,
+        replay: &Replay,
+        over: bool,
+        cursor: (usize, usize),
+        elapsed: Duration,
+        running: bool,
+    ) {
+        let Ok(mut audience) = self.audience.lock() else {
+            return;
+        };
+
+        // Only the moves change the board, and a restarted game has the same board with fewer moves
+        let same_board = self
+            .board
+            .as_ref()
+            .is_some_and(|board| board.is_board_of(replay));
+        let changed = !same_board || replay.events.len() != self.published || over != self.over;
+        if changed {
+            let board = Message::Board {
+                view: View::of(field, replay.seed, over),
+                seconds: elapsed.as_secs_f64(),
+                running,
+                paused: matches!(replay.events.last(), Some(Event::Pause)),
+                row: cursor.0,
+                col: cursor.1,
+            };
+            audience.broadcast(&board);
+            // Spectators that connect from now on start from here
+            audience.game = Some(board);
+            self.board = Some(Board::of(replay));
+            self.published = replay.events.len();
+            self.over = over;
+        } else {
+            if self.cursor != cursor {
+                audience.broadcast(&Message::Cursor {
+                    row: cursor.0,
+                    col: cursor.1,
+                });
+            }
+            // Keeps the game up to date for the spectators that connect later, without building it again
+            if let Some(Message::Board {
+                seconds: s,
+                running: r,
+                row,
+                col,
+                ..
+            }) = &mut audience.game
+            {
+                (*s, *r, *row, *col) = (elapsed.as_secs_f64(), running, cursor.0, cursor.1);
+            }
+        }
+        self.cursor = cursor;
+    }
+}
+
+/// A connection to a game that is being watched
+pub struct Watch {
+    connection: Connection<Message>,
+}
+
+impl Watch {
+    /// Connects to the game published at `addr`. Returns the game so far, as a `Message::Board`
+    pub fn connect(addr: &str) -> Result<(Self, Message)> {
+        let connection = Connection::new(net::connect(addr)?)?;
+        let start = connection.receive()?;
+        if !matches!(start, Message::Board { .. }) {
+            bail!("The game did not start with its board");
+        }
+        Ok((Self { connection }, start))
+    }
+
+    /// Returns the messages received since the last call.
+    /// Returns an error once the player stopped publishing and every message was read
+    pub fn poll(&self) -> Result<Vec<Message>> {
+        let mut messages = Vec::new();
+        loop {
+            match self.connection.try_receive() {
+                Ok(Some(message)) => messages.push(message),
+                Ok(None) => break,
+                // The messages received before are handled first
+                Err(e) if messages.is_empty() => return Err(e),
+                Err(_) => break,
+            }
+        }
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x4 board with mines in the two top corners, opened at the bottom left and with a flag
+    fn field() -> Field {
+        let mut field = Field::new(3, 4);
+        field.set_mines(&[(0, 0), (0, 3)]);
+        field.uncover_at(2, 0);
+        field.toggle_flag_at(0, 3);
+        field
+    }
+
+    #[test]
+    fn spectators_only_get_the_mines_once_the_game_is_over() {
+        let playing = View::of(&field(), Some(7), false);
+        assert_eq!(playing.cells, ["---f", "1111", "0000"]);
+        assert!(playing.mines.is_empty());
+        assert_eq!(playing.seed, None);
+
+        let over = View::of(&field(), Some(7), true);
+        assert_eq!(over.cells, playing.cells);
+        assert_eq!(over.mines, [(0, 0), (0, 3)]);
+        assert_eq!(over.seed, Some(7));
+    }
+
+    #[test]
+    fn spectators_see_the_board_of_the_player() {
+        for over in [false, true] {
+            let view = View::of(&field(), None, over);
+            let watched = view.field();
+            assert_eq!(View::of(&watched, None, over), view);
+            assert_eq!(watched.flag_count, 1);
+            assert_eq!(watched.mine_count, 2);
+            assert_eq!(watched.closed_empty_cells, 2);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::solver::Rating;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GameResult {
+    Won,
+    Lost,
+    /// The player left the board before the game ended, to play a new one or the same one again
+    Abandoned,
+}
+
+/// How much the game helped the player. Games with different assist levels rank separately
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AssistLevel {
+    #[default]
+    None,
+    /// Assisted flagging or the assisted chord, which act on the cell under the cursor
+    Cursor,
+    /// Every move forced by a single number was done automatically across the whole board
+    AutoTrivial,
+}
+
+impl Display for AssistLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssistLevel::None => write!(f, "no assists"),
+            AssistLevel::Cursor => write!(f, "assisted"),
+            AssistLevel::AutoTrivial => write!(f, "auto-solved trivial moves"),
+        }
+    }
+}
+
+/// A single finished game
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GameRecord {
+    /// When the game ended, in seconds since the unix epoch
+    pub timestamp: u64,
+    pub rows: usize,
+    pub cols: usize,
+    pub mines: usize,
+    pub result: GameResult,
+    pub rating: Option<Rating>,
+    #[serde(default)]
+    pub assist: AssistLevel,
+    /// The play time of the game, pauses excluded
+    #[serde(default)]
+    pub seconds: Option<f64>,
+    /// How many times the game was paused, and for how long in total
+    #[serde(default)]
+    pub pauses: usize,
+    #[serde(default)]
+    pub paused_seconds: f64,
+}
+
+impl GameRecord {
+    pub fn new(rows: usize, cols: usize, mines: usize, result: GameResult) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            rows,
+            cols,
+            mines,
+            result,
+            rating: None,
+            assist: AssistLevel::None,
+            seconds: None,
+            pauses: 0,
+            paused_seconds: 0.0,
+        }
+    }
+}
+
+/// The scored attempt at the daily challenge of one day
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailyRecord {
+    /// The day of the challenge, as YYYY-MM-DD
+    pub date: String,
+    /// Abandoned if the player quit before the game ended
+    pub result: GameResult,
+    /// How many empty cells were open when the game ended, out of `empty`
+    pub open: usize,
+    pub empty: usize,
+    /// The play time of the game, pauses excluded
+    pub seconds: f64,
+    #[serde(default)]
+    pub assist: AssistLevel,
+}
+
+/// Returns the directory where mnswpr keeps its data: `$XDG_DATA_HOME/mnswpr`, falling back to `~/.local/share/mnswpr`
+pub fn data_dir() -> Option<PathBuf> {
+    let data_dir = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(data_dir.join("mnswpr"))
+}
+
+/// Every game played on this machine, stored as yaml in the user's data directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Stats {
+    pub games: Vec<GameRecord>,
+    /// The daily challenges that were played, one per day
+    #[serde(default)]
+    pub daily: Vec<DailyRecord>,
+}
+
+impl Stats {
+    /// Returns the path of the stats file: `$XDG_DATA_HOME/mnswpr/stats.yaml`,
+    /// falling back to `~/.local/share/mnswpr/stats.yaml`
+    fn path() -> Option<PathBuf> {
+        Some(data_dir()?.join("stats.yaml"))
+    }
+
+    /// Loads the stats from disk. Returns empty stats if they were never saved
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read_to_string(&path).context("Could not read the stats file.")?;
+        serde_yaml::from_str(&data)
+            .with_context(|| format!("Could not parse the stats file at {}", path.display()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Could not create the stats directory.")?;
+        }
+        fs::write(&path, serde_yaml::to_string(self)?).context("Could not write the stats file.")
+    }
+
+    /// Appends `record` to the stats stored on disk
+    pub fn record(record: GameRecord) -> Result<()> {
+        let mut stats = Self::load()?;
+        stats.games.push(record);
+        stats.save()
+    }
+
+    /// Returns the scored attempt at the daily challenge of `date`, if it was played
+    pub fn daily_record(&self, date: &str) -> Option<&DailyRecord> {
+        self.daily.iter().find(|record| record.date == date)
+    }
+
+    /// Appends `record` to the daily challenges stored on disk, unless its day was already played
+    pub fn record_daily(record: DailyRecord) -> Result<()> {
+        let mut stats = Self::load()?;
+        if stats.daily_record(&record.date).is_none() {
+            stats.daily.push(record);
+            stats.save()?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the daily challenge of the day of `record` stored on disk, which is recorded when the attempt starts
+    pub fn update_daily(record: DailyRecord) -> Result<()> {
+        let mut stats = Self::load()?;
+        match stats.daily.iter_mut().find(|r| r.date == record.date) {
+            Some(stored) => *stored = record,
+            None => stats.daily.push(record),
+        }
+        stats.save()
+    }
+}
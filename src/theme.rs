@@ -0,0 +1,1166 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::cell::{Cell, Content, State};
+use crate::colors::{
+    CBg, CFg, Color, Palette, BG_RESET, FG_RESET, MNSWPR_PALETTE, OG_PALETTE, PLAYER_CURSORS,
+};
+use crate::config::Theme;
+use crate::field::Field;
+use crate::glyphs::{GlyphSet, GlyphsDef};
+
+/// A theme file. Every field can be left out if the theme `extends` another one, which provides the missing fields
+#[derive(Deserialize)]
+struct ThemeFile {
+    extends: Option<String>,
+    closed: Option<PartialElement>,
+    open_bg: Option<CBg>,
+    neighbour_count_to_fg_color: Option<IndexedColors<[CFg; 9]>>,
+    mine: Option<PartialElement>,
+    flag: Option<PartialElement>,
+    /// Optional even without `extends`: questioned cells look like closed ones with a mark unless the theme says otherwise
+    questioned: Option<PartialElement>,
+    cursor_fg: Option<CFg>,
+    /// Optional even without `extends`: the players of a co-op game get the default colors otherwise
+    player_cursor_fg: Option<IndexedColors<[CFg; PLAYER_CURSORS]>>,
+    correct_flag: Option<PartialElement>,
+    wrong_flag: Option<PartialElement>,
+    glyphs: Option<GlyphsDef>,
+}
+
+#[derive(Deserialize)]
+struct PartialElement {
+    fg: Option<CFg>,
+    bg: Option<CBg>,
+}
+
+/// A list of colors, like the ones of the numbers: either all of them in a list,
+/// or only some of them in a map from their index to their color
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IndexedColors<A> {
+    All(A),
+    Some(BTreeMap<usize, CFg>),
+}
+
+impl<const N: usize> IndexedColors<[CFg; N]> {
+    /// Replaces the colors of `base` given here. `name` is the field the colors come from
+    fn apply(self, name: &str, base: &mut [CFg; N]) -> Result<()> {
+        match self {
+            IndexedColors::All(colors) => *base = colors,
+            IndexedColors::Some(colors) => {
+                for (idx, color) in colors {
+                    let Some(fg) = base.get_mut(idx) else {
+                        bail!("{name}[{idx}]: the indices go from 0 to {}", N - 1);
+                    };
+                    *fg = color;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ThemeFile {
+    /// Returns the fields that must be given when the theme does not extend another one, but are missing
+    fn missing_fields(&self) -> Vec<String> {
+        let mut missing = Vec::new();
+        let mut element = |name: &str, element: &Option<PartialElement>| match element {
+            None => missing.push(name.to_string()),
+            Some(e) => {
+                if e.fg.is_none() {
+                    missing.push(format!("{name}.fg"));
+                }
+                if e.bg.is_none() {
+                    missing.push(format!("{name}.bg"));
+                }
+            }
+        };
+        element("closed", &self.closed);
+        element("mine", &self.mine);
+        element("flag", &self.flag);
+        element("correct_flag", &self.correct_flag);
+        element("wrong_flag", &self.wrong_flag);
+        if self.questioned.is_some() {
+            element("questioned", &self.questioned);
+        }
+
+        if self.open_bg.is_none() {
+            missing.push("open_bg".to_string());
+        }
+        if self.cursor_fg.is_none() {
+            missing.push("cursor_fg".to_string());
+        }
+        match &self.neighbour_count_to_fg_color {
+            None => missing.push("neighbour_count_to_fg_color".to_string()),
+            Some(IndexedColors::Some(colors)) => missing.extend(
+                (0..9)
+                    .filter(|count| !colors.contains_key(count))
+                    .map(|count| format!("neighbour_count_to_fg_color[{count}]")),
+            ),
+            Some(IndexedColors::All(_)) => {}
+        }
+        if let Some(IndexedColors::Some(colors)) = &self.player_cursor_fg {
+            missing.extend(
+                (0..PLAYER_CURSORS)
+                    .filter(|player| !colors.contains_key(player))
+                    .map(|player| format!("player_cursor_fg[{player}]")),
+            );
+        }
+        missing
+    }
+
+    /// Returns `base` with the fields given in this file replaced
+    fn apply(self, mut base: Palette) -> Result<Palette> {
+        let elements = [
+            (self.closed, &mut base.closed),
+            (self.mine, &mut base.mine),
+            (self.flag, &mut base.flag),
+            (self.questioned, &mut base.questioned),
+            (self.correct_flag, &mut base.correct_flag),
+            (self.wrong_flag, &mut base.wrong_flag),
+        ];
+        for (element, base) in elements {
+            let Some(element) = element else {
+                continue;
+            };
+            if let Some(fg) = element.fg {
+                base.fg = fg;
+            }
+            if let Some(bg) = element.bg {
+                base.bg = bg;
+            }
+        }
+
+        if let Some(open_bg) = self.open_bg {
+            base.open_bg = open_bg;
+        }
+        if let Some(cursor_fg) = self.cursor_fg {
+            base.cursor_fg = cursor_fg;
+        }
+        if let Some(colors) = self.neighbour_count_to_fg_color {
+            colors.apply(
+                "neighbour_count_to_fg_color",
+                &mut base.neighbour_count_to_fg_color,
+            )?;
+        }
+        if let Some(colors) = self.player_cursor_fg {
+            colors.apply("player_cursor_fg", &mut base.player_cursor_fg)?;
+        }
+        if let Some(glyphs) = self.glyphs {
+            base.glyphs = glyphs.resolve(base.glyphs);
+        }
+        Ok(base)
+    }
+}
+
+/// Loads the theme file at `path`, together with the themes it extends
+pub fn load(path: &Path) -> Result<Palette> {
+    load_file(path, &mut Vec::new())
+}
+
+/// `chain` holds the files that are being loaded because they are extended by another one, to detect cycles
+fn load_file(path: &Path, chain: &mut Vec<PathBuf>) -> Result<Palette> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Could not find the theme \"{}\"", path.display()))?;
+    if chain.contains(&canonical) {
+        bail!("\"{}\" ends up extending itself", path.display());
+    }
+    let source = fs::read_to_string(path).context("Could not read theme data.")?;
+
+    chain.push(canonical);
+    let palette = load_source(&source, path.parent().unwrap_or(Path::new(".")), chain)
+        .with_context(|| format!("Invalid theme \"{}\"", path.display()));
+    chain.pop();
+    palette
+}
+
+/// Loads the theme in `source`. Themes extended by path are looked for relatively to `dir`
+fn load_source(source: &str, dir: &Path, chain: &mut Vec<PathBuf>) -> Result<Palette> {
+    let file: ThemeFile = serde_yaml::from_str(source)?;
+    match &file.extends {
+        Some(name) => {
+            let base = extended_palette(name, dir, chain)?;
+            file.apply(base)
+        }
+        None => {
+            let missing = file.missing_fields();
+            if !missing.is_empty() {
+                bail!(
+                    "missing {}. Add them, or take them from another theme with `extends`",
+                    missing.join(", ")
+                );
+            }
+            let questioned = file.questioned.is_some();
+            let mut palette = file.apply(MNSWPR_PALETTE)?;
+            if !questioned {
+                palette.questioned.bg = palette.closed.bg.clone();
+            }
+            Ok(palette)
+        }
+    }
+}
+
+/// Returns the palette of `name`, the theme extended by a theme file in `dir`.
+/// `name` is either a built-in theme, a path relative to `dir`, or the name of a user theme
+fn extended_palette(name: &str, dir: &Path, chain: &mut Vec<PathBuf>) -> Result<Palette> {
+    match name.to_lowercase().as_str() {
+        "mnswpr" => Ok(MNSWPR_PALETTE),
+        "og" => Ok(OG_PALETTE),
+        _ => {
+            let path = dir.join(name);
+            if path.exists() {
+                load_file(&path, chain)
+            } else {
+                load_file(&find(name).unwrap_or(path), chain)
+            }
+        }
+    }
+}
+
+/// Returns the directory of the user themes: `$XDG_CONFIG_HOME/mnswpr/themes`,
+/// falling back to `~/.config/mnswpr/themes`
+pub fn themes_dir() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("mnswpr").join("themes"))
+}
+
+/// Returns the path of the user theme called `name`, if there is one
+pub fn find(name: &str) -> Option<PathBuf> {
+    let path = themes_dir()?.join(format!("{name}.yaml"));
+    path.is_file().then_some(path)
+}
+
+/// Returns the names and the paths of the user themes, sorted by name
+pub fn user_themes() -> Vec<(String, PathBuf)> {
+    let Some(entries) = themes_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut themes: Vec<(String, PathBuf)> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if !path.is_file() || path.extension()? != "yaml" {
+                return None;
+            }
+            Some((path.file_stem()?.to_string_lossy().to_string(), path))
+        })
+        .collect();
+    themes.sort();
+    themes
+}
+
+/// The themes that can be switched between during a game: the built-in ones, the user themes and the one chosen at startup
+pub struct ThemeCycle {
+    themes: Vec<Theme>,
+    current: usize,
+    /// Adapts the palettes to the options and to the colors supported by the terminal
+    adapt: Box<dyn Fn(Palette) -> Palette>,
+    /// If active, the current theme gets loaded again whenever its file changes
+    pub watch: bool,
+    /// When the file of the current theme was modified the last time it was loaded
+    modified: Option<SystemTime>,
+}
+
+impl ThemeCycle {
+    pub fn new(current: &Theme, watch: bool, adapt: impl Fn(Palette) -> Palette + 'static) -> Self {
+        let mut themes = vec![Theme::Mnswpr, Theme::OG];
+        themes.extend(
+            user_themes()
+                .into_iter()
+                .map(|(_, path)| Theme::Custom(path.to_string_lossy().to_string())),
+        );
+        let current = match themes.iter().position(|theme| theme == current) {
+            Some(idx) => idx,
+            None => {
+                themes.push(current.clone());
+                themes.len() - 1
+            }
+        };
+
+        let mut cycle = Self {
+            themes,
+            current,
+            adapt: Box::new(adapt),
+            watch,
+            modified: None,
+        };
+        cycle.modified = cycle.last_modified();
+        cycle
+    }
+
+    pub fn current(&self) -> &Theme {
+        &self.themes[self.current]
+    }
+
+    fn last_modified(&self) -> Option<SystemTime> {
+        match self.current() {
+            Theme::Custom(path) => fs::metadata(path).and_then(|m| m.modified()).ok(),
+            _ => None,
+        }
+    }
+
+    /// Switches to the next theme that can be loaded and whose palette is accepted by `fits`, and returns its palette.
+    /// Returns an error, keeping the current theme, if there is no such theme
+    pub fn next(&mut self, fits: impl Fn(&Palette) -> bool) -> Result<Palette> {
+        let start = self.current;
+        for _ in 1..self.themes.len() {
+            self.current = (self.current + 1) % self.themes.len();
+            let Ok(palette) = self.current().to_palette() else {
+                continue;
+            };
+            let palette = (self.adapt)(palette);
+            if fits(&palette) {
+                self.modified = self.last_modified();
+                return Ok(palette);
+            }
+        }
+        self.current = start;
+        bail!("No other theme can be loaded and fits in the terminal")
+    }
+
+    /// If the file of the current theme changed since it was loaded, returns its palette loaded again
+    pub fn reload(&mut self) -> Option<Result<Palette>> {
+        let modified = self.last_modified();
+        if !self.watch || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(self.current().to_palette().map(&self.adapt))
+    }
+}
+
+/// The kind of value expected in a field of a theme
+enum Kind {
+    Color,
+    Element,
+    /// A list of exactly this many colors
+    Colors(usize),
+    Glyphs,
+}
+
+/// The fields of a theme, and whether they are required
+const FIELDS: [(&str, Kind, bool); 11] = [
+    ("closed", Kind::Element, true),
+    ("open_bg", Kind::Color, true),
+    ("neighbour_count_to_fg_color", Kind::Colors(9), true),
+    ("mine", Kind::Element, true),
+    ("flag", Kind::Element, true),
+    ("questioned", Kind::Element, false),
+    ("cursor_fg", Kind::Color, true),
+    ("player_cursor_fg", Kind::Colors(PLAYER_CURSORS), false),
+    ("correct_flag", Kind::Element, true),
+    ("wrong_flag", Kind::Element, true),
+    ("glyphs", Kind::Glyphs, false),
+];
+
+const GLYPH_FIELDS: [&str; 8] = [
+    "closed",
+    "flag",
+    "questioned",
+    "mine",
+    "correct_flag",
+    "wrong_flag",
+    "cursor_left",
+    "cursor_right",
+];
+
+/// Below this contrast ratio, a glyph is considered hard to read on its background
+const MIN_CONTRAST: f64 = 1.5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Severity {
+    /// The theme cannot be loaded
+    Error,
+    /// The theme can be loaded, but something about it is probably not intended
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Something wrong found in a theme file
+pub struct Problem {
+    pub severity: Severity,
+    /// The path of the field with the problem, e.g. `mine.fg` or `neighbour_count_to_fg_color[3]`
+    pub path: String,
+    /// The line of the field, starting from 1, if it could be found in the file
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.severity)?;
+        if let Some(line) = self.line {
+            write!(f, " at line {line}")?;
+        }
+        if !self.path.is_empty() {
+            write!(f, " in {}", self.path)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+#[derive(Clone)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn path_to_string(path: &[Segment]) -> String {
+    let mut s = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(key) if s.is_empty() => s.push_str(key),
+            Segment::Key(key) => s.push_str(&format!(".{key}")),
+            Segment::Index(idx) => s.push_str(&format!("[{idx}]")),
+        }
+    }
+    s
+}
+
+/// Checks the theme in `source`, a file in `dir`, and returns every problem found in it.
+/// The theme can be loaded only if none of the problems is an error
+pub fn check(source: &str, dir: &Path) -> Vec<Problem> {
+    let mut checker = Checker {
+        source,
+        problems: Vec::new(),
+        complete: true,
+    };
+
+    let root = match serde_yaml::from_str::<Value>(source) {
+        Ok(root) => root,
+        Err(e) => {
+            checker.problems.push(Problem {
+                severity: Severity::Error,
+                path: String::new(),
+                line: e.location().map(|l| l.line()),
+                message: format!("the file is not valid yaml: {e}"),
+            });
+            return checker.problems;
+        }
+    };
+    let Some(map) = root.as_mapping() else {
+        checker.report(
+            Severity::Error,
+            &[],
+            "expected a map with the fields of the theme",
+        );
+        return checker.problems;
+    };
+
+    let mut known = FIELDS.map(|(name, _, _)| name).to_vec();
+    known.push("extends");
+    checker.unknown_keys(&[], map, &known);
+
+    // Without `extends`, every field must be given
+    checker.complete = true;
+    if let Some(extends) = map.get("extends") {
+        checker.complete = false;
+        let path = [Segment::Key("extends".to_string())];
+        match extends.as_str() {
+            Some(name) => {
+                if let Err(e) = extended_palette(name, dir, &mut Vec::new()) {
+                    checker.report(Severity::Error, &path, format!("{e:#}"));
+                }
+            }
+            None => checker.report(
+                Severity::Error,
+                &path,
+                "expected the name or the path of a theme",
+            ),
+        }
+    }
+
+    for (name, kind, required) in FIELDS {
+        let path = [Segment::Key(name.to_string())];
+        match map.get(name) {
+            Some(value) => checker.value(&path, value, &kind),
+            None if required && checker.complete => checker.missing(&path),
+            None => {}
+        }
+    }
+
+    if !checker
+        .problems
+        .iter()
+        .any(|p| p.severity == Severity::Error)
+    {
+        if let Ok(palette) = load_source(source, dir, &mut Vec::new()) {
+            checker.contrast(&palette);
+        }
+    }
+    checker.problems
+}
+
+struct Checker<'a> {
+    source: &'a str,
+    problems: Vec<Problem>,
+    /// Whether every field must be given, that is whether the theme does not extend another one
+    complete: bool,
+}
+
+impl Checker<'_> {
+    /// Adds a problem. If the field is not in the file, e.g. because it is missing, the line of its parent is used
+    fn report(&mut self, severity: Severity, path: &[Segment], message: impl Into<String>) {
+        let line = (1..=path.len())
+            .rev()
+            .find_map(|len| line_of(self.source, &path[..len]));
+        self.problems.push(Problem {
+            severity,
+            path: path_to_string(path),
+            line,
+            message: message.into(),
+        });
+    }
+
+    fn missing(&mut self, path: &[Segment]) {
+        self.report(
+            Severity::Error,
+            path,
+            "missing field. Add it, or take it from another theme with `extends`",
+        );
+    }
+
+    fn unknown_keys(&mut self, path: &[Segment], map: &serde_yaml::Mapping, known: &[&str]) {
+        for key in map.keys() {
+            match key.as_str() {
+                Some(key) if known.contains(&key) => {}
+                Some(key) => self.report(
+                    Severity::Warning,
+                    &child(path, key),
+                    "unknown field, it is ignored",
+                ),
+                None => self.report(
+                    Severity::Error,
+                    path,
+                    format!("the keys must be strings, got {}", describe(key)),
+                ),
+            }
+        }
+    }
+
+    fn value(&mut self, path: &[Segment], value: &Value, kind: &Kind) {
+        match kind {
+            Kind::Color => {
+                if let Err(e) = serde_yaml::from_value::<CFg>(value.clone()) {
+                    self.report(Severity::Error, path, e.to_string());
+                }
+            }
+            Kind::Element => {
+                let Some(map) = value.as_mapping() else {
+                    self.report(Severity::Error, path, "expected a map with `fg` and `bg`");
+                    return;
+                };
+                self.unknown_keys(path, map, &["fg", "bg"]);
+                for name in ["fg", "bg"] {
+                    let path = child(path, name);
+                    match map.get(name) {
+                        Some(value) => self.value(&path, value, &Kind::Color),
+                        None if self.complete => self.missing(&path),
+                        None => {}
+                    }
+                }
+            }
+            Kind::Colors(len) => match value {
+                Value::Sequence(seq) => {
+                    if seq.len() != *len {
+                        self.report(
+                            Severity::Error,
+                            path,
+                            format!("expected a list of {len} colors, got {}", seq.len()),
+                        );
+                    }
+                    for (idx, value) in seq.iter().enumerate().take(*len) {
+                        self.value(&index(path, idx), value, &Kind::Color);
+                    }
+                }
+                Value::Mapping(map) => {
+                    let mut given = vec![false; *len];
+                    for (key, value) in map {
+                        match key.as_u64().map(|idx| idx as usize).filter(|idx| idx < len) {
+                            Some(idx) => {
+                                given[idx] = true;
+                                self.value(&index(path, idx), value, &Kind::Color);
+                            }
+                            None => self.report(
+                                Severity::Error,
+                                path,
+                                format!(
+                                    "expected numbers from 0 to {} as keys, got {}",
+                                    len - 1,
+                                    describe(key)
+                                ),
+                            ),
+                        }
+                    }
+                    let missing: Vec<String> = (0..*len)
+                        .filter(|idx| !given[*idx])
+                        .map(|idx| idx.to_string())
+                        .collect();
+                    if self.complete && !missing.is_empty() {
+                        self.report(
+                            Severity::Error,
+                            path,
+                            format!(
+                                "missing the colors of {}. Add them, or take them from another theme with `extends`",
+                                missing.join(", ")
+                            ),
+                        );
+                    }
+                }
+                _ => self.report(
+                    Severity::Error,
+                    path,
+                    format!("expected a list of {len} colors, or a map from numbers to colors"),
+                ),
+            },
+            Kind::Glyphs => match value {
+                Value::String(name) => {
+                    if let Err(e) = GlyphSet::from_str(name) {
+                        self.report(Severity::Error, path, e);
+                    }
+                }
+                Value::Mapping(map) => {
+                    let mut known = GLYPH_FIELDS.to_vec();
+                    known.push("base");
+                    self.unknown_keys(path, map, &known);
+
+                    for (key, value) in map {
+                        let Some(key) = key.as_str().filter(|key| known.contains(key)) else {
+                            continue;
+                        };
+                        let path = child(path, key);
+                        match value.as_str() {
+                            Some(name) if key == "base" => {
+                                if let Err(e) = GlyphSet::from_str(name) {
+                                    self.report(Severity::Error, &path, e);
+                                }
+                            }
+                            Some(_) => {}
+                            None => self.report(Severity::Error, &path, "expected a string"),
+                        }
+                    }
+                }
+                _ => self.report(
+                    Severity::Error,
+                    path,
+                    "expected the name of a glyph set or a map of glyphs",
+                ),
+            },
+        }
+    }
+
+    /// Warns about glyphs that would be hard to read on their background, and cells that could not be told apart.
+    /// Pairs of colors that both come from the extended theme are not its fault, so they are left alone
+    fn contrast(&mut self, palette: &Palette) {
+        let key = |name: &str| vec![Segment::Key(name.to_string())];
+        let open_bg = key("open_bg");
+
+        for (count, fg) in palette
+            .neighbour_count_to_fg_color
+            .iter()
+            .enumerate()
+            .skip(1)
+        {
+            self.low_contrast(
+                [&index(&key("neighbour_count_to_fg_color"), count), &open_bg],
+                fg,
+                &palette.open_bg,
+                &format!("{count}s would be hard to read"),
+            );
+        }
+
+        let elements = [
+            ("closed", &palette.closed),
+            ("mine", &palette.mine),
+            ("flag", &palette.flag),
+            ("questioned", &palette.questioned),
+            ("correct_flag", &palette.correct_flag),
+            ("wrong_flag", &palette.wrong_flag),
+        ];
+        for (name, element) in elements {
+            self.low_contrast(
+                [&child(&key(name), "fg"), &child(&key(name), "bg")],
+                &element.fg,
+                &element.bg,
+                &format!("the glyphs of {name} cells would be hard to read"),
+            );
+        }
+
+        let closed_bg = child(&key("closed"), "bg");
+        for (path, bg) in [
+            (&closed_bg, &palette.closed.bg),
+            (&open_bg, &palette.open_bg),
+        ] {
+            self.low_contrast(
+                [&key("cursor_fg"), path],
+                &palette.cursor_fg,
+                bg,
+                "the cursor would be hard to see",
+            );
+            for (player, fg) in palette.player_cursor_fg.iter().enumerate() {
+                self.low_contrast(
+                    [&index(&key("player_cursor_fg"), player), path],
+                    fg,
+                    bg,
+                    "the cursor of a co-op player would be hard to see",
+                );
+            }
+        }
+
+        if palette.closed.bg.0 == palette.open_bg.0 && self.given([&open_bg, &closed_bg]).is_some()
+        {
+            self.report(
+                Severity::Warning,
+                &open_bg,
+                "same color as closed.bg, closed and empty open cells would look the same",
+            );
+        }
+    }
+
+    /// Returns the first of `paths` that is given in the file
+    fn given<'p>(&self, paths: [&'p Vec<Segment>; 2]) -> Option<&'p Vec<Segment>> {
+        paths
+            .into_iter()
+            .find(|path| line_of(self.source, path).is_some())
+    }
+
+    /// `paths` are the paths of `fg` and `bg`
+    fn low_contrast(&mut self, paths: [&Vec<Segment>; 2], fg: &CFg, bg: &CBg, what: &str) {
+        let ratio = contrast_ratio(fg.0, bg.0);
+        if ratio >= MIN_CONTRAST {
+            return;
+        }
+        if let Some(path) = self.given(paths) {
+            self.report(
+                Severity::Warning,
+                path,
+                format!(
+                    "low contrast ({ratio:.1}:1) between {} and {}, {what}",
+                    path_to_string(paths[0]),
+                    path_to_string(paths[1])
+                ),
+            );
+        }
+    }
+}
+
+/// Returns `value` as it would be written in the file
+fn describe(value: &Value) -> String {
+    serde_yaml::to_string(value)
+        .map(|s| s.trim_end().to_string())
+        .unwrap_or_default()
+}
+
+fn index(path: &[Segment], idx: usize) -> Vec<Segment> {
+    let mut path = path.to_vec();
+    path.push(Segment::Index(idx));
+    path
+}
+
+fn child(path: &[Segment], key: &str) -> Vec<Segment> {
+    let mut path = path.to_vec();
+    path.push(Segment::Key(key.to_string()));
+    path
+}
+
+/// Returns the contrast ratio between two colors, as defined by the WCAG: 1 for the same color, up to 21 for black and white
+fn contrast_ratio(a: Color, b: Color) -> f64 {
+    let luminance = |color: Color| {
+        let Color::Rgb(r, g, b) = color else {
+            return 0.0;
+        };
+        let linear = |c: u8| {
+            let c = c as f64 / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+    };
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Marks the error raised by `Locator` once it reaches its target
+const FOUND: &str = "\0found";
+
+/// Returns the line of the field at `path` in `source`.
+/// The yaml gets deserialized by a visitor that walks down `path` and fails as soon as it reaches the field,
+/// so that the error carries the position of the field
+fn line_of(source: &str, path: &[Segment]) -> Option<usize> {
+    if path.is_empty() {
+        return None;
+    }
+    let locator = Locator { path };
+    match locator.deserialize(serde_yaml::Deserializer::from_str(source)) {
+        Err(e) if e.to_string().contains(FOUND) => e.location().map(|l| l.line()),
+        _ => None,
+    }
+}
+
+struct Locator<'a> {
+    path: &'a [Segment],
+}
+
+impl<'de> DeserializeSeed<'de> for Locator<'_> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        if self.path.is_empty() {
+            deserializer.deserialize_any(Found)
+        } else {
+            deserializer.deserialize_any(self)
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for Locator<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map or a list")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<(), A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let Some((segment, rest)) = self.path.split_first() else {
+            return Ok(());
+        };
+        // Numbers can be keys too
+        let key = match segment {
+            Segment::Key(key) => key.clone(),
+            Segment::Index(idx) => idx.to_string(),
+        };
+        let last = rest.is_empty();
+        while let Some(matches) = map.next_key_seed(KeyLocator { key: &key, last })? {
+            if matches {
+                map.next_value_seed(Locator { path: rest })?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let Some((Segment::Index(idx), rest)) = self.path.split_first() else {
+            return Err(de::Error::custom("not a map"));
+        };
+        for _ in 0..*idx {
+            if seq.next_element::<IgnoredAny>()?.is_none() {
+                return Ok(());
+            }
+        }
+        seq.next_element_seed(Locator { path: rest })?;
+        Ok(())
+    }
+}
+
+/// Deserializes a key, returning whether it is `key`. Fails if it is, and it is the `last` segment of the path
+struct KeyLocator<'a> {
+    key: &'a str,
+    last: bool,
+}
+
+impl<'de> DeserializeSeed<'de> for KeyLocator<'_> {
+    type Value = bool;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeyLocator<'_> {
+    type Value = bool;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    // The error must be raised here rather than after deserializing the key, otherwise it gets the position of the map
+    fn visit_str<E>(self, v: &str) -> Result<bool, E>
+    where
+        E: de::Error,
+    {
+        match v == self.key {
+            true if self.last => Err(E::custom(FOUND)),
+            matches => Ok(matches),
+        }
+    }
+}
+
+/// Fails on any value. The error always contains `FOUND`, since it is what the visitor expects
+struct Found;
+
+impl<'de> Visitor<'de> for Found {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(FOUND)
+    }
+}
+
+/// The mines of the preview board, which shows every neighbour count from 0 to 8
+const PREVIEW_MINES: [(usize, usize); 16] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 0),
+    (1, 2),
+    (1, 4),
+    (2, 0),
+    (2, 1),
+    (2, 2),
+    (2, 4),
+    (2, 5),
+    (2, 6),
+    (3, 3),
+    (3, 4),
+];
+
+/// Returns a small board drawn with `palette`: on the left while being played, on the right as shown after a loss
+pub fn preview(palette: &Palette) -> String {
+    let mut field = Field::new(4, 10);
+    field.set_mines(&PREVIEW_MINES);
+    for row in 0..field.rows {
+        for col in 0..field.cols {
+            let state = match (row, col) {
+                (0, 0) | (0, 1) | (1, 0) | (1, 7) => State::Flagged,
+                (2, 5) => State::Open,
+                (0, 8) => State::Closed,
+                (1, 9) => State::Questioned,
+                _ if field.get_unchecked(row, col).contains_mine() => State::Closed,
+                _ => State::Open,
+            };
+            field.get_mut_unchecked(row, col).set_state(state);
+        }
+    }
+
+    let mut s = String::new();
+    for row in 0..field.rows {
+        for lost in [false, true] {
+            for col in 0..field.cols {
+                let cell = *field.get_unchecked(row, col);
+                let with_cursor = (row, col) == (1, 1);
+                s.push_str(&if lost {
+                    cell.to_string_with_palette_lost(palette, with_cursor)
+                } else {
+                    cell.to_string_with_palette(palette, with_cursor)
+                });
+            }
+            s.push_str(&format!("{BG_RESET}{FG_RESET}"));
+            if !lost {
+                s.push_str("    ");
+            }
+        }
+        s.push_str("\r\n");
+    }
+    s
+}
+
+/// Returns a single line showing how `palette` draws a closed cell, a flag, a questioned cell,
+/// the numbers from 1 to 8 and a mine
+pub fn sample(palette: &Palette) -> String {
+    let cell = |state, content, neighbouring_bomb_count| Cell {
+        state,
+        content,
+        neighbouring_bomb_count,
+    };
+
+    let mut cells = vec![
+        cell(State::Closed, Content::Empty, 0),
+        cell(State::Flagged, Content::Mine, 0),
+        cell(State::Questioned, Content::Empty, 0),
+    ];
+    cells.extend((1..=8).map(|count| cell(State::Open, Content::Empty, count)));
+    cells.push(cell(State::Open, Content::Mine, 0));
+
+    let mut s: String = cells
+        .into_iter()
+        .map(|cell| cell.to_string_with_palette(palette, false))
+        .collect();
+    s.push_str(&format!("{BG_RESET}{FG_RESET}"));
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A theme without any problem, which the tests break in different ways
+    const THEME: &str = "\
+closed:
+  fg: '#ffffff'
+  bg: '#101010'
+open_bg: '#404040'
+neighbour_count_to_fg_color:
+  - '#404040'
+  - '#5080ff'
+  - '#40c040'
+  - '#ff0000'
+  - '#c080ff'
+  - '#ffa040'
+  - '#40e0e0'
+  - '#ffffff'
+  - '#c0c0c0'
+mine:
+  fg: '#000000'
+  bg: '#ff0000'
+flag:
+  fg: '#ffffff'
+  bg: '#0000ff'
+cursor_fg: '#ffff00'
+correct_flag:
+  fg: '#000000'
+  bg: '#00ff00'
+wrong_flag:
+  fg: '#ffffff'
+  bg: '#ff0000'
+";
+
+    fn described(problems: &[Problem]) -> Vec<String> {
+        problems.iter().map(Problem::to_string).collect()
+    }
+
+    #[test]
+    fn a_good_theme_has_no_problems() {
+        assert!(check(THEME, Path::new(".")).is_empty());
+        assert!(check(include_str!("../example_theme.yaml"), Path::new("."))
+            .iter()
+            .all(|p| p.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn invalid_yaml_is_reported_with_its_line() {
+        let problems = check("closed:\n  fg: [\nopen_bg: '#000000'\n", Path::new("."));
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].severity, Severity::Error);
+        assert!(problems[0].line.is_some());
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_its_field() {
+        let theme = THEME
+            .replace("  - '#ff0000'\n", "  - '#ff00'\n")
+            .replace("cursor_fg:", "cursor_color:")
+            .replace("flag:\n  fg: '#ffffff'\n  bg: '#0000ff'\n", "");
+        assert_eq!(
+            described(&check(&theme, Path::new("."))),
+            [
+                "warning at line 18 in cursor_color: unknown field, it is ignored",
+                "error at line 9 in neighbour_count_to_fg_color[3]: \
+                 \"#ff00\" has 4 digits, expected a color in the format #rrggbb or #rgb",
+                "error in flag: missing field. Add it, or take it from another theme with `extends`",
+                "error in cursor_fg: missing field. Add it, or take it from another theme with `extends`",
+            ]
+        );
+    }
+
+    #[test]
+    fn glyphs_that_are_hard_to_read_are_warned_about() {
+        let theme = THEME.replace(
+            "  fg: '#000000'\n  bg: '#ff0000'",
+            "  fg: '#ee0000'\n  bg: '#ff0000'",
+        );
+        let problems = check(&theme, Path::new("."));
+        assert_eq!(
+            described(&problems),
+            [
+                "warning at line 16 in mine.fg: low contrast (1.1:1) between mine.fg and mine.bg, \
+              the glyphs of mine cells would be hard to read"
+            ]
+        );
+    }
+
+    #[test]
+    fn extending_themes_only_give_the_fields_they_change() {
+        let source = "extends: og\nmine:\n  fg: '#123'\nneighbour_count_to_fg_color:\n  3: navy\n";
+        assert!(check(source, Path::new(".")).is_empty());
+
+        let palette = load_source(source, Path::new("."), &mut Vec::new()).unwrap();
+        assert_eq!(palette.mine.fg.0, Color::Rgb(0x11, 0x22, 0x33));
+        assert_eq!(palette.mine.bg.0, OG_PALETTE.mine.bg.0);
+        assert_eq!(palette.open_bg.0, OG_PALETTE.open_bg.0);
+        assert_eq!(
+            palette.neighbour_count_to_fg_color[3].0,
+            Color::Rgb(0, 0, 128)
+        );
+        assert_eq!(
+            palette.neighbour_count_to_fg_color[2].0,
+            OG_PALETTE.neighbour_count_to_fg_color[2].0
+        );
+    }
+
+    #[test]
+    fn themes_that_extend_nothing_must_be_complete() {
+        let error = load_source("mine:\n  fg: '#123'\n", Path::new("."), &mut Vec::new())
+            .err()
+            .unwrap();
+        assert!(error
+            .to_string()
+            .starts_with("missing closed, mine.bg, flag,"));
+    }
+
+    #[test]
+    fn extended_theme_files_are_found_next_to_the_theme() {
+        let dir = std::env::temp_dir().join(format!("mnswpr-extends-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("base.yaml"), THEME).unwrap();
+        fs::write(
+            dir.join("child.yaml"),
+            "extends: base.yaml\nopen_bg: '#ddd'\n",
+        )
+        .unwrap();
+        fs::write(dir.join("loop.yaml"), "extends: loop.yaml\n").unwrap();
+
+        let palette = load(&dir.join("child.yaml"));
+        let cycle = load(&dir.join("loop.yaml"));
+        let missing = check("extends: missing.yaml\n", &dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let palette = palette.unwrap();
+        assert_eq!(palette.open_bg.0, Color::Rgb(0xdd, 0xdd, 0xdd));
+        assert_eq!(palette.closed.bg.0, Color::Rgb(0x10, 0x10, 0x10));
+        assert!(format!("{:#}", cycle.err().unwrap()).contains("ends up extending itself"));
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].path, "extends");
+        assert_eq!(missing[0].line, Some(1));
+    }
+}
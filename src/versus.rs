@@ -0,0 +1,305 @@
+//! Two players racing on the same board over TCP, with no server in between:
+//! one runs `mnswpr host` and the other one `mnswpr join <addr>`.
+//!
+//! The host generates the board and sends it to the other player. Both players then tell each other how many cells
+//! they opened after every move, and the host decides who won: the first player to clear the board,
+//! or the one that opened more cells when both hit a mine
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::net::{self, Connection};
+use crate::replay::Replay;
+
+/// The width of the opponent progress bar, in terminal columns
+const BAR_WIDTH: usize = 10;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Side {
+    Host,
+    Guest,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Message {
+    /// The board of the race, sent by the host as soon as the other player joins
+    Board { replay: Replay },
+    /// How many cells a player opened, and whether they are still playing
+    Progress { open: usize, alive: bool },
+    /// Who won the race, sent by the host once it is decided. None for a draw
+    Result { winner: Option<Side> },
+}
+
+/// How the race ended for this player
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RaceResult {
+    Won,
+    Lost,
+    Draw,
+    /// The other player quit, or the connection was lost
+    OpponentLeft,
+}
+
+#[derive(Copy, Clone)]
+struct Progress {
+    open: usize,
+    alive: bool,
+}
+
+pub struct Versus {
+    connection: Connection<Message>,
+    side: Side,
+    /// The number of empty cells of the board, which have to be opened to clear it
+    empty: usize,
+    mine: Progress,
+    opponent: Progress,
+    /// None while the race is still going
+    result: Option<RaceResult>,
+}
+
+impl Versus {
+    fn new(connection: Connection<Message>, side: Side, replay: &Replay) -> Self {
+        let start = Progress {
+            open: replay.open.len(),
+            alive: true,
+        };
+        Self {
+            connection,
+            side,
+            empty: replay.rows * replay.cols - replay.mines.len(),
+            mine: start,
+            opponent: start,
+            result: None,
+        }
+    }
+
+    /// Waits for the other player to join on `port`, then sends them the board of the race
+    pub fn host(port: u16, replay: &Replay) -> Result<Self> {
+        let listener = net::listen(port)?;
+        println!("Waiting for the other player to join on port {port}, press Ctrl-C to cancel");
+        let (stream, addr) = listener.accept()?;
+        println!("{addr} joined");
+
+        let mut connection = Connection::new(stream)?;
+        connection.send(&Message::Board {
+            replay: replay.clone(),
+        })?;
+        Ok(Self::new(connection, Side::Host, replay))
+    }
+
+    /// Joins the race hosted at `addr`. Returns the board of the race
+    pub fn join(addr: &str) -> Result<(Self, Replay)> {
+        let connection = Connection::new(net::connect(addr)?)?;
+        let Message::Board { replay } = connection.receive()? else {
+            bail!("The host did not send the board");
+        };
+        Ok((Self::new(connection, Side::Guest, &replay), replay))
+    }
+
+    /// Returns how the race ended, or None if it is still going
+    pub fn result(&self) -> Option<RaceResult> {
+        self.result
+    }
+
+    /// Handles the messages of the other player received since the last call
+    pub fn poll(&mut self) {
+        loop {
+            match self.connection.try_receive() {
+                Ok(Some(Message::Progress { open, alive })) => {
+                    self.opponent = Progress { open, alive };
+                    if self.side == Side::Host {
+                        self.decide(Side::Guest);
+                    }
+                }
+                Ok(Some(Message::Result { winner })) => {
+                    self.result.get_or_insert(match winner {
+                        Some(side) if side == self.side => RaceResult::Won,
+                        Some(_) => RaceResult::Lost,
+                        None => RaceResult::Draw,
+                    });
+                }
+                Ok(Some(Message::Board { .. })) => {}
+                Ok(None) => return,
+                Err(_) => {
+                    self.result.get_or_insert(RaceResult::OpponentLeft);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Tells the other player how many cells this player opened, and whether they are still playing
+    pub fn report(&mut self, open: usize, alive: bool) {
+        if self.mine.open == open && self.mine.alive == alive {
+            return;
+        }
+        self.mine = Progress { open, alive };
+        if self
+            .connection
+            .send(&Message::Progress { open, alive })
+            .is_err()
+        {
+            self.result.get_or_insert(RaceResult::OpponentLeft);
+            return;
+        }
+        if self.side == Side::Host {
+            self.decide(Side::Host);
+        }
+    }
+
+    /// Decides the race if it is over, after the progress of `updated` changed, and tells the other player.
+    /// Only the host decides, so that both players always agree on who cleared the board first
+    fn decide(&mut self, updated: Side) {
+        if self.result.is_some() {
+            return;
+        }
+
+        let (updated_progress, other_progress) = match updated {
+            Side::Host => (self.mine, self.opponent),
+            Side::Guest => (self.opponent, self.mine),
+        };
+        let winner = if updated_progress.open == self.empty {
+            Some(updated)
+        } else if !updated_progress.alive && !other_progress.alive {
+            match self.mine.open.cmp(&self.opponent.open) {
+                std::cmp::Ordering::Greater => Some(Side::Host),
+                std::cmp::Ordering::Less => Some(Side::Guest),
+                std::cmp::Ordering::Equal => None,
+            }
+        } else {
+            return;
+        };
+
+        self.result = Some(match winner {
+            Some(Side::Host) => RaceResult::Won,
+            Some(Side::Guest) => RaceResult::Lost,
+            None => RaceResult::Draw,
+        });
+        if self.connection.send(&Message::Result { winner }).is_err() {
+            self.result = Some(RaceResult::OpponentLeft);
+        }
+    }
+
+    /// Returns the progress of the other player, drawn next to the header:
+    /// a bar of the cleared percentage of the board and whether they are still playing
+    pub fn opponent_bar(&self) -> String {
+        let percentage = self.opponent.open * 100 / self.empty.max(1);
+        let filled = self.opponent.open * BAR_WIDTH / self.empty.max(1);
+        let status = if self.result == Some(RaceResult::OpponentLeft) {
+            "left"
+        } else if self.opponent.open == self.empty {
+            "cleared"
+        } else if self.opponent.alive {
+            "alive"
+        } else {
+            "dead"
+        };
+        format!(
+            "Opponent:[{}{}] {percentage}% {status}",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+
+    use super::*;
+
+    /// A race on a board with 8 empty cells, and the connection of the other player
+    fn race(side: Side) -> (Versus, Connection<Message>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let theirs = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (ours, _) = listener.accept().unwrap();
+        let replay = Replay {
+            rows: 2,
+            cols: 5,
+            mines: vec![(0, 0), (1, 4)],
+            ..Default::default()
+        };
+        (
+            Versus::new(Connection::new(ours).unwrap(), side, &replay),
+            Connection::new(theirs).unwrap(),
+        )
+    }
+
+    #[test]
+    fn the_host_decides_races_that_are_over() {
+        use RaceResult::*;
+
+        // The side whose progress changed, the progress of the host and of the guest as (open, alive),
+        // and the result for the host
+        let cases = [
+            (Side::Host, (8, true), (5, true), Some(Won)),
+            (Side::Guest, (5, true), (8, true), Some(Lost)),
+            (Side::Host, (8, true), (8, true), Some(Won)),
+            (Side::Host, (6, false), (2, true), None),
+            (Side::Guest, (6, true), (2, false), None),
+            (Side::Guest, (3, false), (6, false), Some(Lost)),
+            (Side::Host, (6, false), (3, false), Some(Won)),
+            (Side::Host, (4, false), (4, false), Some(Draw)),
+        ];
+        for (updated, (open, alive), (their_open, their_alive), expected) in cases {
+            let (mut versus, opponent) = race(Side::Host);
+            versus.mine = Progress { open, alive };
+            versus.opponent = Progress {
+                open: their_open,
+                alive: their_alive,
+            };
+            versus.decide(updated);
+            assert_eq!(versus.result(), expected, "{updated:?} {open} {their_open}");
+
+            let winner = match expected {
+                Some(Won) => Some(Side::Host),
+                Some(Lost) => Some(Side::Guest),
+                _ => None,
+            };
+            if expected.is_some() {
+                assert!(matches!(
+                    opponent.receive().unwrap(),
+                    Message::Result { winner: w } if w == winner
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn the_race_is_decided_once() {
+        let (mut versus, _opponent) = race(Side::Host);
+        versus.mine = Progress {
+            open: 8,
+            alive: true,
+        };
+        versus.decide(Side::Host);
+        versus.opponent = versus.mine;
+        versus.decide(Side::Guest);
+        assert_eq!(versus.result(), Some(RaceResult::Won));
+    }
+
+    #[test]
+    fn opponent_bar_shows_the_progress_of_the_other_player() {
+        let (mut versus, _opponent) = race(Side::Guest);
+        let mut bars = Vec::new();
+        for (open, alive) in [(0, true), (4, true), (6, false), (8, true)] {
+            versus.opponent = Progress { open, alive };
+            bars.push(versus.opponent_bar());
+        }
+        versus.result = Some(RaceResult::OpponentLeft);
+        bars.push(versus.opponent_bar());
+
+        assert_eq!(
+            bars,
+            [
+                "Opponent:[----------] 0% alive",
+                "Opponent:[#####-----] 50% alive",
+                "Opponent:[#######---] 75% dead",
+                "Opponent:[##########] 100% cleared",
+                "Opponent:[##########] 100% left",
+            ]
+        );
+    }
+}